@@ -0,0 +1,118 @@
+//! Frame conversion throughput, `cargo bench --bench convert`
+//!
+//! Compares the conversion module with the per pixel `yuyv444_to_rgb` loop it replaces, and its
+//! AVX2 path with the portable one it falls back to.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bevy_gstreamer::camera::PixelFormat;
+use bevy_gstreamer::convert::{Colorimetry, Plane, RawFrame, convert, convert_portable};
+use bevy_gstreamer::types::yuyv444_to_rgb;
+
+const SIZES: [(usize, usize); 2] = [(640, 480), (1920, 1080)];
+
+fn bench(name: &str, width: usize, height: usize, mut run: impl FnMut()) {
+    run();
+    let start = Instant::now();
+    let mut frames = 0;
+    while start.elapsed() < Duration::from_secs(1) {
+        run();
+        frames += 1;
+    }
+    let per_frame = start.elapsed() / frames;
+    let megapixels = (width * height) as f64 / per_frame.as_secs_f64() / 1e6;
+    println!("{name:<24} {width}x{height}  {per_frame:>10.2?}/frame  {megapixels:>8.1} MP/s");
+}
+
+/// deterministic noise, so no frame is all one color
+fn noise(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491_u32;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+fn main() {
+    for (width, height) in SIZES {
+        let packed = noise(width * height * 2);
+        let luma = noise(width * height);
+        let chroma = noise(width * height / 2);
+        let mut rgb = vec![0; width * height * 3];
+        let mut rgba = vec![0; width * height * 4];
+        let (w, h) = (width as u32, height as u32);
+
+        bench("scalar yuyv -> rgb", width, height, || {
+            for (yuyv, dest) in packed.chunks_exact(4).zip(rgb.chunks_exact_mut(6)) {
+                let (u, v) = (i32::from(yuyv[1]), i32::from(yuyv[3]));
+                dest[..3].copy_from_slice(&yuyv444_to_rgb(i32::from(yuyv[0]), u, v));
+                dest[3..].copy_from_slice(&yuyv444_to_rgb(i32::from(yuyv[2]), u, v));
+            }
+            black_box(&rgb);
+        });
+
+        let frames = [
+            ("yuyv", RawFrame::Yuyv(Plane::new(&packed, width * 2))),
+            ("uyvy", RawFrame::Uyvy(Plane::new(&packed, width * 2))),
+            (
+                "nv12",
+                RawFrame::Nv12 {
+                    y: Plane::new(&luma, width),
+                    uv: Plane::new(&chroma, width),
+                },
+            ),
+            (
+                "i420",
+                RawFrame::I420 {
+                    y: Plane::new(&luma, width),
+                    u: Plane::new(&chroma[..chroma.len() / 2], width / 2),
+                    v: Plane::new(&chroma[chroma.len() / 2..], width / 2),
+                },
+            ),
+            ("gray", RawFrame::Gray(Plane::new(&luma, width))),
+        ];
+        for (name, frame) in frames {
+            bench(&format!("{name} -> rgb"), width, height, || {
+                let dest = black_box(&mut rgb);
+                convert(
+                    &frame,
+                    w,
+                    h,
+                    dest,
+                    PixelFormat::Rgb8,
+                    Colorimetry::default(),
+                )
+                .unwrap();
+            });
+            bench(&format!("{name} -> rgba"), width, height, || {
+                let dest = black_box(&mut rgba);
+                convert(
+                    &frame,
+                    w,
+                    h,
+                    dest,
+                    PixelFormat::Rgba8,
+                    Colorimetry::default(),
+                )
+                .unwrap();
+            });
+            bench(&format!("{name} -> rgb portable"), width, height, || {
+                let dest = black_box(&mut rgb);
+                convert_portable(
+                    &frame,
+                    w,
+                    h,
+                    dest,
+                    PixelFormat::Rgb8,
+                    Colorimetry::default(),
+                )
+                .unwrap();
+            });
+        }
+    }
+}
@@ -1,8 +1,11 @@
 use bevy::prelude::*;
 
 use bevy_gstreamer::GstreamerPlugin;
-use bevy_gstreamer::camera::{BackgroundImageMarker, GstCamera};
-use bevy_gstreamer::types::{CameraFormat, FrameFormat};
+use bevy_gstreamer::camera::{
+    BackgroundFit, CameraBackground, GstCamera, GstCameraFailed, GstCameraSource, MjpegDecoder,
+    MjpegDecoding, VideoMaterial, VideoMaterialSource,
+};
+use bevy_gstreamer::types::{CameraFormat, FrameFormat, KnownCameraControl};
 
 fn main() {
     App::new()
@@ -17,7 +20,7 @@ fn main() {
         }))
         .add_plugins(GstreamerPlugin)
         .add_systems(Startup, setup_camera)
-        .add_systems(Update, camera_control)
+        .add_systems(Update, (camera_control, camera_errors))
         .run();
 }
 
@@ -25,15 +28,14 @@ fn setup_camera(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    mut video_materials: ResMut<Assets<VideoMaterial>>,
 ) {
-    let mut camera = GstCamera::new(
-        0,
-        Some(CameraFormat::new_from(640, 480, FrameFormat::MJPEG, 30)),
-    )
-    .expect("cannot find any camera");
-
-    camera.open_stream().unwrap();
-    commands.spawn((camera, BackgroundImageMarker));
+    let source = commands
+        .spawn(GstCameraSource::new(
+            0,
+            Some(CameraFormat::new_from(640, 480, FrameFormat::MJPEG, 30)),
+        ))
+        .id();
 
     // cube
     commands.spawn((
@@ -42,6 +44,20 @@ fn setup_camera(
         Transform::from_xyz(0.0, 0.5, 0.0),
     ));
 
+    // screen showing the camera
+    commands.spawn((
+        Mesh3d(meshes.add(Rectangle::new(1.6, 1.2))),
+        MeshMaterial3d(video_materials.add(VideoMaterial {
+            base: StandardMaterial {
+                unlit: true,
+                ..default()
+            },
+            extension: default(),
+        })),
+        VideoMaterialSource { source },
+        Transform::from_xyz(1.5, 1.0, 0.0),
+    ));
+
     // light
     commands.spawn((
         PointLight {
@@ -54,13 +70,12 @@ fn setup_camera(
     commands.spawn((
         Camera3d::default(),
         Transform::from_xyz(-2.5, 4.5, 9.0).looking_at(Vec3::ZERO, Vec3::Y),
+        CameraBackground { source },
+        BackgroundFit::Contain(Color::BLACK),
     ));
 }
 
-fn camera_control(
-    keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut q_camera: Query<&mut GstCamera, With<BackgroundImageMarker>>,
-) {
+fn camera_control(keyboard_input: Res<ButtonInput<KeyCode>>, mut q_camera: Query<&mut GstCamera>) {
     if keyboard_input.just_pressed(KeyCode::Digit1) {
         if let Ok(mut cam) = q_camera.single_mut() {
             info!("start stream");
@@ -90,4 +105,32 @@ fn camera_control(
                 .unwrap();
         }
     }
+
+    if keyboard_input.just_pressed(KeyCode::Digit5) {
+        if let Ok(mut cam) = q_camera.single_mut() {
+            info!("lock exposure");
+            if let Err(e) = cam.set_auto(KnownCameraControl::Exposure, false) {
+                warn!("{e}");
+            }
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Digit6) {
+        if let Ok(mut cam) = q_camera.single_mut() {
+            let decoder = match cam.mjpeg_decoding().decoder {
+                MjpegDecoder::Cpu => MjpegDecoder::Auto,
+                _ => MjpegDecoder::Cpu,
+            };
+            info!("decode MJPEG with {decoder:?}");
+            if let Err(e) = cam.set_mjpeg_decoding(MjpegDecoding::new(decoder)) {
+                warn!("{e}");
+            }
+        }
+    }
+}
+
+fn camera_errors(mut events: EventReader<GstCameraFailed>) {
+    for event in events.read() {
+        error!("cannot open camera: {}", event.error);
+    }
 }
@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::prelude::*;
+
+use bevy_gstreamer::camera::{GstCamera, GstCameraFailed, GstCameraSource, WebCameraPlugin};
+
+fn main() {
+    App::new()
+        .add_plugins(
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+                1.0 / 30.0,
+            ))),
+        )
+        .add_plugins(bevy::log::LogPlugin::default())
+        .add_plugins(WebCameraPlugin)
+        .add_systems(Startup, |mut commands: Commands| {
+            commands.spawn(GstCameraSource::default());
+        })
+        .add_systems(Update, (analyze_frames, camera_errors))
+        .run();
+}
+
+fn analyze_frames(mut cameras: Query<&mut GstCamera>) {
+    for mut camera in &mut cameras {
+        let brightness = camera.with_frame(|frame| {
+            let sum: u64 = frame.data().iter().map(|v| *v as u64).sum();
+            sum / frame.data().len().max(1) as u64
+        });
+        if let Ok(Some(brightness)) = brightness {
+            info!("mean brightness {brightness}");
+        }
+    }
+}
+
+fn camera_errors(mut events: EventReader<GstCameraFailed>, mut exit: EventWriter<AppExit>) {
+    for event in events.read() {
+        error!("cannot open camera: {}", event.error);
+        exit.write(AppExit::error());
+    }
+}
@@ -1,79 +1,132 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
-use bevy::core_pipeline;
 use bevy::prelude::*;
-use bevy::render::RenderApp;
-use bevy::render::extract_resource::ExtractResourcePlugin;
-use bevy::render::render_graph::RenderGraph;
 use glib::Quark;
 use gstreamer::prelude::*;
 use gstreamer::{
-    Bin, Caps, ClockTime, DeviceMonitor, Element, FlowError, FlowSuccess, MessageView,
-    ResourceError, State, element_error,
+    Bin, Caps, ClockTime, Device, DeviceMonitor, Element, FlowError, FlowSuccess, MessageView,
+    ResourceError, State, Structure, StructureRef, element_error,
     prelude::{DeviceExt, DeviceMonitorExt, DeviceMonitorExtManual, ElementExt, GstBinExt},
 };
 use gstreamer_app::{AppSink, AppSinkCallbacks};
-use gstreamer_video::{VideoFormat, VideoInfo};
+use gstreamer_video::{VideoColorMatrix, VideoColorRange, VideoFormat, VideoInfo};
 use image::ImageBuffer;
 use image::Rgb;
 use regex::Regex;
 
-use crate::camera::background::*;
+use crate::camera::encoded::{ENCODED_SINK, EncodedQueue};
+use crate::camera::sync::{FrameQueue, QueuedFrame};
+use crate::convert::{ColorMatrix, ColorRange, Colorimetry, Plane, RawFrame, convert};
 use crate::error::BevyGstError;
-use crate::types::{CameraFormat, CameraInfo, FrameFormat, mjpeg_to_rgb24};
-use crate::types::{Resolution, yuyv422_to_rgb};
+#[cfg(feature = "cpu-mjpeg")]
+use crate::types::mjpeg_to_rgb24;
+use crate::types::{
+    CameraConfig, CameraControl, CameraFormat, CameraIndex, CameraInfo, ControlKind, FrameFormat,
+    KnownCameraControl, Orientation, Resolution,
+};
 
-type PipelineGenRet = (Element, AppSink, Arc<Mutex<ImageBuffer<Rgb<u8>, Vec<u8>>>>);
+type PipelineGenRet = (
+    Element,
+    AppSink,
+    Arc<Mutex<FrameQueue>>,
+    Arc<Mutex<EncodedQueue>>,
+);
+type RgbFrame = ImageBuffer<Rgb<u8>, Vec<u8>>;
 
+#[cfg(feature = "background")]
 mod background;
-
+#[cfg(feature = "render")]
+mod chroma_key;
+mod control;
+mod decode;
+mod encoded;
+mod frame;
+#[cfg(feature = "background")]
+mod grading;
+#[cfg(feature = "render")]
+mod material;
+#[cfg(feature = "mock")]
+mod mock;
+mod output;
+#[cfg(feature = "render")]
+mod render;
+mod source;
+mod sync;
+
+#[cfg(feature = "background")]
+pub use background::{
+    BackgroundBlend, BackgroundComposite, BackgroundFit, BackgroundOrder, BackgroundShader,
+    CameraBackground,
+};
+#[cfg(feature = "render")]
+pub use chroma_key::ChromaKey;
+pub use decode::{MjpegDecoder, MjpegDecoding};
+pub use encoded::{EncodedPassthrough, EncodedSample};
+pub use frame::{FrameView, PixelFormat};
+#[cfg(feature = "background")]
+pub use grading::{GstColorGrading, load_cube_lut};
+#[cfg(feature = "render")]
+pub use material::{VideoMaterial, VideoMaterialExtension, VideoMaterialSource};
+#[cfg(feature = "mock")]
+pub use mock::{MockCamera, MockFrames};
+pub use output::GstCameraImage;
+#[cfg(feature = "render")]
+pub use render::CameraRenderPlugin;
+pub use source::{GstCameraError, GstCameraFailed, GstCameraSource};
+pub use sync::{FrameSync, SyncMode};
+
+/// Capture from cameras, works in headless apps without a `RenderApp`
+///
+/// Add `CameraRenderPlugin` from the `render` feature to draw camera backgrounds and video materials.
 pub struct WebCameraPlugin;
 
 impl Plugin for WebCameraPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(BackgroundImage(Image::default()))
-            .add_plugins(ExtractResourcePlugin::<BackgroundImage>::default())
-            .add_systems(Update, handle_background_image);
-
-        let render_app = app.sub_app_mut(RenderApp);
-        let background_node_2d = BackgroundNode::new(render_app.world_mut());
-        let background_node_3d = BackgroundNode::new(render_app.world_mut());
-        let mut render_graph = render_app.world_mut().resource_mut::<RenderGraph>();
-
-        if let Some(graph_2d) =
-            render_graph.get_sub_graph_mut(core_pipeline::core_2d::graph::Core2d)
-        {
-            graph_2d.add_node(BackgroundNodeLabel, background_node_2d);
-
-            graph_2d.add_node_edge(
-                BackgroundNodeLabel,
-                core_pipeline::core_2d::graph::Node2d::StartMainPass,
+        app.register_type::<CameraConfig>()
+            .register_type::<CameraInfo>()
+            .register_type::<CameraIndex>()
+            .register_type::<CameraFormat>()
+            .register_type::<Resolution>()
+            .register_type::<FrameFormat>()
+            .register_type::<CameraControl>()
+            .register_type::<KnownCameraControl>()
+            .register_type::<ControlKind>()
+            .register_type::<Orientation>()
+            .register_type::<MjpegDecoding>()
+            .register_type::<MjpegDecoder>()
+            .register_type::<EncodedPassthrough>()
+            .register_type::<GstCameraOptions>()
+            .register_type::<Colorimetry>()
+            .register_type::<ColorMatrix>()
+            .register_type::<ColorRange>()
+            .register_type::<GstCameraSource>()
+            .add_event::<GstCameraFailed>()
+            .add_observer(source::open_camera_source)
+            .add_observer(source::close_camera_source::<GstCameraSource>)
+            .add_systems(
+                Update,
+                output::update_camera_images.run_if(resource_exists::<Assets<Image>>),
             );
-        }
-
-        if let Some(graph_3d) =
-            render_graph.get_sub_graph_mut(core_pipeline::core_3d::graph::Core3d)
-        {
-            graph_3d.add_node(BackgroundNodeLabel, background_node_3d);
-
-            graph_3d.add_node_edge(
-                BackgroundNodeLabel,
-                core_pipeline::core_3d::graph::Node3d::MainTransparentPass,
-            );
-        }
-    }
-
-    fn finish(&self, app: &mut App) {
-        let render_app = app.sub_app_mut(RenderApp);
-        render_app.init_resource::<BackgroundPipeline>();
+        #[cfg(feature = "mock")]
+        app.add_observer(mock::open_mock_camera)
+            .add_observer(source::close_camera_source::<MockCamera>);
     }
 }
 
-#[derive(Component)]
-pub struct BackgroundImageMarker;
+/// How the camera pipeline is built, pass them when opening to build it only once
+#[derive(Copy, Clone, Debug, Default, PartialEq, Reflect)]
+pub struct GstCameraOptions {
+    pub orientation: Orientation,
+    /// where MJPEG frames are decoded
+    pub mjpeg_decoding: MjpegDecoding,
+    /// YUV matrix and range replacing the ones of the caps
+    pub colorimetry: Option<Colorimetry>,
+    /// hand out samples before decoding
+    pub encoded_passthrough: EncodedPassthrough,
+}
 
 /// A camera from gstreamer pipeline
 #[derive(Component)]
@@ -84,33 +137,151 @@ pub struct GstCamera {
     app_sink: AppSink,
     camera_format: CameraFormat,
     camera_info: CameraInfo,
-    image_lock: Arc<Mutex<ImageBuffer<Rgb<u8>, Vec<u8>>>>,
+    custom_source: Option<String>,
+    /// frames pushed into the `appsrc` of a [`MockCamera`]
+    #[cfg(feature = "mock")]
+    mock: Option<Arc<mock::MockFeed>>,
+    frame_sync: FrameSync,
+    options: GstCameraOptions,
+    frame_queue: Arc<Mutex<FrameQueue>>,
+    encoded_queue: Arc<Mutex<EncodedQueue>>,
+    /// end of stream or error taken off the bus, reported until the stream is stopped
+    bus_error: Option<BevyGstError>,
     caps: Option<Caps>,
+    extra_controls: Structure,
+    control_values: BTreeMap<KnownCameraControl, i32>,
 }
 
 impl GstCamera {
-    pub fn new(index: usize, format: Option<CameraFormat>) -> Result<Self, BevyGstError> {
+    /// open a camera by index, display name, device path or stable id
+    pub fn new(
+        index: impl Into<CameraIndex>,
+        format: Option<CameraFormat>,
+    ) -> Result<Self, BevyGstError> {
+        Self::with_options(index, format, GstCameraOptions::default())
+    }
+
+    /// open a camera like [`GstCamera::new`] with its pipeline built from `options`
+    pub fn with_options(
+        index: impl Into<CameraIndex>,
+        format: Option<CameraFormat>,
+        options: GstCameraOptions,
+    ) -> Result<Self, BevyGstError> {
+        let index = index.into();
         let camera_format = format.unwrap_or_default();
 
         if let Err(why) = gstreamer::init() {
             return Err(BevyGstError::InitializeError(why.to_string()));
         }
 
-        let (camera_info, caps) = search_device(index)?;
+        let (camera_info, caps) = search_device(&index)?;
 
-        let (pipeline, app_sink, receiver) = generate_pipeline(camera_format, index)?;
+        Self::open(camera_info, None, caps, camera_format, options)
+    }
+
+    /// open a camera reading from a gstreamer source instead of a device
+    ///
+    /// `source` is a pipeline description producing the stream, e.g.
+    /// `videotestsrc pattern=smpte`, the caps of `format` are appended to it.
+    /// Name the element `source` to reach its controls.
+    pub fn from_source(
+        source: impl Into<String>,
+        format: Option<CameraFormat>,
+    ) -> Result<Self, BevyGstError> {
+        Self::from_source_with_options(source, format, GstCameraOptions::default())
+    }
+
+    /// open a camera like [`GstCamera::from_source`] with its pipeline built from `options`
+    pub fn from_source_with_options(
+        source: impl Into<String>,
+        format: Option<CameraFormat>,
+        options: GstCameraOptions,
+    ) -> Result<Self, BevyGstError> {
+        if let Err(why) = gstreamer::init() {
+            return Err(BevyGstError::InitializeError(why.to_string()));
+        }
+
+        let source = source.into();
+        let camera_info = CameraInfo::new(&source, "Custom source", "", 0);
+        Self::open(
+            camera_info,
+            Some(source),
+            None,
+            format.unwrap_or_default(),
+            options,
+        )
+    }
+
+    fn open(
+        camera_info: CameraInfo,
+        custom_source: Option<String>,
+        caps: Option<Caps>,
+        camera_format: CameraFormat,
+        options: GstCameraOptions,
+    ) -> Result<Self, BevyGstError> {
+        let frame_sync = FrameSync::default();
+        let source = pipeline_source(custom_source.as_deref(), &camera_info, camera_format);
+        let (pipeline, app_sink, receiver, encoded_queue) =
+            generate_pipeline(&source, camera_format, &camera_info, &frame_sync, options)?;
 
         Ok(Self {
-            index,
+            index: camera_info.index(),
             pipeline,
             app_sink,
             camera_format,
             camera_info,
-            image_lock: receiver,
+            custom_source,
+            #[cfg(feature = "mock")]
+            mock: None,
+            frame_sync,
+            options,
+            frame_queue: receiver,
+            encoded_queue,
+            bus_error: None,
             caps,
+            extra_controls: Structure::new_empty("c"),
+            control_values: BTreeMap::new(),
         })
     }
 
+    /// open a camera from a saved [`CameraConfig`]
+    ///
+    /// Controls the device does not support are skipped with a warning.
+    pub fn from_config(config: &CameraConfig) -> Result<Self, BevyGstError> {
+        let options = GstCameraOptions {
+            orientation: config.orientation,
+            ..Default::default()
+        };
+        let mut camera = Self::with_options(config.device.clone(), Some(config.format), options)?;
+
+        // auto modes first, manual values are rejected while their auto mode is on
+        let (auto, manual): (Vec<_>, Vec<_>) = config
+            .controls
+            .iter()
+            .partition(|(control, _)| control.auto_control() == Some(**control));
+        for (control, value) in auto.into_iter().chain(manual) {
+            if let Err(why) = camera.set_control(*control, *value) {
+                warn!("skip camera control {control}: {why}");
+            }
+        }
+
+        Ok(camera)
+    }
+
+    /// the current device, format and controls, to be saved and restored with [`GstCamera::from_config`]
+    pub fn config(&self) -> CameraConfig {
+        CameraConfig {
+            // prefer the stable id, device indices may change between runs
+            device: match self.camera_info.id() {
+                Some(id) => CameraIndex::String(id.to_string()),
+                None => CameraIndex::Index(self.index as u32),
+            },
+            format: self.camera_format,
+            orientation: self.options.orientation,
+            controls: self.control_values.clone(),
+        }
+    }
+
     /// open capture stream
     pub fn open_stream(&mut self) -> Result<(), BevyGstError> {
         if let Err(why) = self.pipeline.set_state(State::Playing) {
@@ -128,25 +299,80 @@ impl GstCamera {
     }
 
     /// get rgb image from device
+    ///
+    /// The frame is picked according to the camera [`FrameSync`].
     pub fn frame(&mut self) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, BevyGstError> {
-        let cam_fmt = self.camera_format;
-        let image_data = self.frame_raw()?;
-        let imagebuf =
-            match ImageBuffer::from_vec(cam_fmt.width(), cam_fmt.height(), image_data.to_vec()) {
-                Some(buf) => {
-                    let rgb: ImageBuffer<Rgb<u8>, Vec<u8>> = buf;
-                    rgb
-                }
-                None => return Err(BevyGstError::ReadFrameError(
-                    "Imagebuffer is not large enough! This is probably a bug, please report it!"
-                        .to_string(),
-                )),
-            };
-        Ok(imagebuf)
+        self.poll_bus()?;
+
+        let now = self.pipeline.current_running_time();
+        let mut queue = self.frame_queue.lock().unwrap();
+        match queue.present(&self.frame_sync, now) {
+            Some(image) => Ok(image.clone()),
+            None => Err(BevyGstError::ReadFrameError(
+                "No frame is due yet".to_string(),
+            )),
+        }
+    }
+
+    /// get the next rgb image from device, `None` when no new frame is due
+    pub fn poll_frame(&mut self) -> Result<Option<RgbFrame>, BevyGstError> {
+        self.poll_bus()?;
+
+        let now = self.pipeline.current_running_time();
+        let sync = FrameSync {
+            repeat: false,
+            ..self.frame_sync
+        };
+        let mut queue = self.frame_queue.lock().unwrap();
+        Ok(queue.present(&sync, now).cloned())
+    }
+
+    /// lend the current rgb frame to `f` without copying it
+    ///
+    /// The frame is picked like [`GstCamera::frame`], `None` is returned when no frame is due.
+    /// The camera callback blocks while `f` runs, so keep it short or copy what you need.
+    pub fn with_frame<R>(
+        &mut self,
+        f: impl FnOnce(FrameView<'_>) -> R,
+    ) -> Result<Option<R>, BevyGstError> {
+        self.poll_bus()?;
+
+        let now = self.pipeline.current_running_time();
+        let mut queue = self.frame_queue.lock().unwrap();
+        Ok(queue
+            .present(&self.frame_sync, now)
+            .map(|image| f(FrameView::from_rgb(image))))
+    }
+
+    /// take the encoded samples received since the last call, oldest first
+    ///
+    /// Empty unless [`EncodedPassthrough`] is on. At most 256 samples are kept, call this every
+    /// frame to not lose any.
+    pub fn encoded_samples(&mut self) -> Result<Vec<EncodedSample>, BevyGstError> {
+        self.poll_bus()?;
+        Ok(self.encoded_queue.lock().unwrap().drain(..).collect())
     }
 
     /// raw data from device
-    pub fn frame_raw(&mut self) -> Result<Cow<[u8]>, BevyGstError> {
+    pub fn frame_raw(&mut self) -> Result<Cow<'_, [u8]>, BevyGstError> {
+        Ok(Cow::from(self.frame()?.into_raw()))
+    }
+
+    /// the newest due frame if it changed since the last call, leaving it to [`GstCamera::frame`]
+    pub(crate) fn peek_new_frame(&mut self) -> Option<RgbFrame> {
+        let now = self.pipeline.current_running_time();
+        let mut queue = self.frame_queue.lock().unwrap();
+        queue.peek_new(&self.frame_sync, now).cloned()
+    }
+
+    /// report end of stream and errors posted on the pipeline bus
+    ///
+    /// They are kept, later calls report them too until the stream is stopped.
+    pub(crate) fn poll_bus(&mut self) -> Result<(), BevyGstError> {
+        if let Some(error) = &self.bus_error {
+            return Err(error.clone());
+        }
+
         let bus = match self.pipeline.bus() {
             Some(bus) => bus,
             None => {
@@ -157,21 +383,20 @@ impl GstCamera {
         };
 
         if let Some(message) = bus.timed_pop(ClockTime::from_seconds(0)) {
-            match message.view() {
+            let error = match message.view() {
                 MessageView::Eos(..) => {
-                    return Err(BevyGstError::ReadFrameError("Stream is ended!".to_string()));
+                    BevyGstError::ReadFrameError("Stream is ended!".to_string())
                 }
                 MessageView::Error(err) => {
-                    return Err(BevyGstError::ReadFrameError(format!(
-                        "Bus error: {}",
-                        err.error()
-                    )));
+                    BevyGstError::ReadFrameError(format!("Bus error: {}", err.error()))
                 }
-                _ => {}
-            }
+                _ => return Ok(()),
+            };
+            self.bus_error = Some(error.clone());
+            return Err(error);
         }
 
-        Ok(Cow::from(self.image_lock.lock().unwrap().to_vec()))
+        Ok(())
     }
 
     /// stop device stream
@@ -182,6 +407,9 @@ impl GstCamera {
                 why
             )));
         }
+        self.frame_queue.lock().unwrap().clear();
+        self.encoded_queue.lock().unwrap().clear();
+        self.bus_error = None;
         Ok(())
     }
 
@@ -200,32 +428,229 @@ impl GstCamera {
         self.camera_format
     }
 
+    /// get how frames are presented
+    pub fn frame_sync(&self) -> FrameSync {
+        self.frame_sync
+    }
+
+    /// set how frames are presented
+    pub fn set_frame_sync(&mut self, frame_sync: FrameSync) {
+        self.app_sink.set_sync(frame_sync.mode == SyncMode::Clock);
+        self.frame_queue
+            .lock()
+            .unwrap()
+            .set_capacity(frame_sync.queue_size);
+        self.frame_sync = frame_sync;
+    }
+
+    /// get how the pipeline is built
+    pub fn options(&self) -> GstCameraOptions {
+        self.options
+    }
+
+    /// change how the pipeline is built, rebuilds it once for all changes
+    pub fn set_options(&mut self, options: GstCameraOptions) -> Result<(), BevyGstError> {
+        self.rebuild_pipeline(self.camera_format, options)?;
+        self.options = options;
+        Ok(())
+    }
+
+    /// get where MJPEG frames are decoded
+    pub fn mjpeg_decoding(&self) -> MjpegDecoding {
+        self.options.mjpeg_decoding
+    }
+
+    /// decode MJPEG frames in the appsink or with gstreamer elements, rebuilds the pipeline
+    pub fn set_mjpeg_decoding(&mut self, decoding: MjpegDecoding) -> Result<(), BevyGstError> {
+        self.set_options(GstCameraOptions {
+            mjpeg_decoding: decoding,
+            ..self.options
+        })
+    }
+
+    /// get the colorimetry replacing the one of the caps, `None` to use the caps
+    pub fn colorimetry(&self) -> Option<Colorimetry> {
+        self.options.colorimetry
+    }
+
+    /// convert YUV frames with `colorimetry` instead of the one the caps announce, rebuilds the pipeline
+    ///
+    /// Helps cameras announcing the wrong matrix or range, frames decoded by gstreamer elements are
+    /// not affected.
+    pub fn set_colorimetry(
+        &mut self,
+        colorimetry: Option<Colorimetry>,
+    ) -> Result<(), BevyGstError> {
+        self.set_options(GstCameraOptions {
+            colorimetry,
+            ..self.options
+        })
+    }
+
+    /// get whether samples are handed out before decoding
+    pub fn encoded_passthrough(&self) -> EncodedPassthrough {
+        self.options.encoded_passthrough
+    }
+
+    /// hand out samples before decoding with [`GstCamera::encoded_samples`], rebuilds the pipeline
+    ///
+    /// H.264 and H.265 samples are whole access units with parameter sets before every keyframe.
+    pub fn set_encoded_passthrough(
+        &mut self,
+        encoded_passthrough: EncodedPassthrough,
+    ) -> Result<(), BevyGstError> {
+        self.set_options(GstCameraOptions {
+            encoded_passthrough,
+            ..self.options
+        })
+    }
+
     /// set camera a new format
     pub fn set_camera_format(&mut self, new_fmt: CameraFormat) -> Result<(), BevyGstError> {
+        self.rebuild_pipeline(new_fmt, self.options)?;
+        self.camera_format = new_fmt;
+        Ok(())
+    }
+
+    /// get the orientation applied to frames
+    pub fn orientation(&self) -> Orientation {
+        self.options.orientation
+    }
+
+    /// mirror or rotate frames, rotations by 90 degrees swap the frame width and height
+    pub fn set_orientation(&mut self, orientation: Orientation) -> Result<(), BevyGstError> {
+        self.set_options(GstCameraOptions {
+            orientation,
+            ..self.options
+        })
+    }
+
+    fn rebuild_pipeline(
+        &mut self,
+        fmt: CameraFormat,
+        options: GstCameraOptions,
+    ) -> Result<(), BevyGstError> {
         let mut reopen = false;
         if self.is_stream_open() {
-            println!("is open");
-
+            debug!(
+                "restart the open stream of {}",
+                self.camera_info.human_name()
+            );
             self.stop_stream()?;
             reopen = true;
         }
-        let (pipeline, app_sink, receiver) = generate_pipeline(new_fmt, self.camera_info.index())?;
+        let source = pipeline_source(self.custom_source.as_deref(), &self.camera_info, fmt);
+        let (pipeline, app_sink, receiver, encoded_queue) =
+            generate_pipeline(&source, fmt, &self.camera_info, &self.frame_sync, options)?;
         self.pipeline = pipeline;
         self.app_sink = app_sink;
-        self.image_lock = receiver;
+        self.frame_queue = receiver;
+        self.encoded_queue = encoded_queue;
+        self.bus_error = None;
+        // custom sources may have no element named `source`
+        if let Ok(source) = self.source() {
+            control::apply_extra_controls(&source, &self.extra_controls);
+        }
+        #[cfg(feature = "mock")]
+        if let Some(feed) = &self.mock {
+            feed.attach(&self.source()?)?;
+        }
         if reopen {
             self.open_stream()?;
         }
+        Ok(())
+    }
 
-        self.camera_format = new_fmt;
+    /// list the hardware controls of the device with their ranges, defaults and values
+    pub fn controls(&self) -> Result<Vec<CameraControl>, BevyGstError> {
+        control::list_controls(&self.source()?)
+    }
+
+    /// get a hardware control of the device
+    pub fn control(&self, control: KnownCameraControl) -> Result<CameraControl, BevyGstError> {
+        match self.controls()?.into_iter().find(|c| c.control == control) {
+            Some(c) => Ok(c),
+            None => Err(BevyGstError::GetPropertyError {
+                property: control.to_string(),
+                error: "Device has no such control".to_string(),
+            }),
+        }
+    }
+
+    /// set a hardware control of the device
+    ///
+    /// Values are kept and applied again when the pipeline is rebuilt.
+    pub fn set_control(
+        &mut self,
+        control: KnownCameraControl,
+        value: i32,
+    ) -> Result<(), BevyGstError> {
+        let description = self.control(control)?;
+        control::set_control(
+            &self.source()?,
+            &mut self.extra_controls,
+            &description,
+            value,
+        )?;
+        self.control_values.insert(control, value);
         Ok(())
     }
 
+    /// toggle the auto mode of a control, e.g. to lock exposure
+    pub fn set_auto(
+        &mut self,
+        control: KnownCameraControl,
+        enabled: bool,
+    ) -> Result<(), BevyGstError> {
+        let auto = match control.auto_control() {
+            Some(auto) => auto,
+            None => {
+                return Err(BevyGstError::SetPropertyError {
+                    property: control.to_string(),
+                    value: format!("auto {enabled}"),
+                    error: "Control has no auto mode".to_string(),
+                });
+            }
+        };
+        let description = self.control(auto)?;
+        self.set_control(auto, control::auto_value(&description, enabled))
+    }
+
+    /// the capture source element of the pipeline
+    pub(crate) fn source(&self) -> Result<Element, BevyGstError> {
+        match self
+            .pipeline
+            .clone()
+            .dynamic_cast::<Bin>()
+            .ok()
+            .and_then(|bin| bin.by_name("source"))
+        {
+            Some(source) => Ok(source),
+            None => Err(BevyGstError::GeneralError(
+                "Failed to get source element!".to_string(),
+            )),
+        }
+    }
+
+    /// list the formats the device offers, errors for cameras opened from a custom source
+    pub fn compatible_formats(&self) -> Result<Vec<FrameFormat>, BevyGstError> {
+        let Some(caps) = &self.caps else {
+            return Err(BevyGstError::GetPropertyError {
+                property: "Device Caps".to_string(),
+                error: "No device caps!".to_string(),
+            });
+        };
+        Ok(FrameFormat::ALL
+            .into_iter()
+            .filter(|format| caps.iter().any(|structure| caps_match(structure, *format)))
+            .collect())
+    }
+
+    /// list the resolutions the device offers in `fourcc` with their integer framerates
     #[allow(clippy::too_many_lines)]
     #[allow(clippy::cast_sign_loss)]
-    #[allow(dead_code)]
-    fn compatible_list_by_resolution(
-        &mut self,
+    pub fn compatible_list_by_resolution(
+        &self,
         fourcc: FrameFormat,
     ) -> Result<HashMap<Resolution, Vec<u32>>, BevyGstError> {
         let mut resolution_map = HashMap::new();
@@ -238,196 +663,96 @@ impl GstCamera {
         match self.caps.clone() {
             Some(c) => {
                 for capability in c.iter() {
-                    match fourcc {
-                        FrameFormat::MJPEG => {
-                            if capability.name() == "image/jpeg" {
-                                let mut fps_vec = vec![];
+                    if caps_match(capability, fourcc) {
+                        let mut fps_vec = vec![];
 
-                                let width = match capability.get::<i32>("width") {
-                                    Ok(w) => w,
-                                    Err(why) => {
-                                        return Err(BevyGstError::GetPropertyError {
-                                            property: "Capibilities by Resolution: Width"
-                                                .to_string(),
-                                            error: why.to_string(),
-                                        });
-                                    }
-                                };
-                                let height = match capability.get::<i32>("height") {
-                                    Ok(w) => w,
-                                    Err(why) => {
-                                        return Err(BevyGstError::GetPropertyError {
-                                            property: "Capibilities by Resolution: Height"
-                                                .to_string(),
-                                            error: why.to_string(),
-                                        });
-                                    }
-                                };
-                                let value =
-                                    match capability.value_by_quark(Quark::from_str("framerate")) {
-                                        Ok(v) => match v.transform::<String>() {
-                                            Ok(s) => {
-                                                format!("{:?}", s)
-                                            }
-                                            Err(why) => {
-                                                return Err(BevyGstError::GetPropertyError {
-                                                    property: "Framerates".to_string(),
-                                                    error: format!(
-                                                        "Failed to make framerates into string: {}",
-                                                        why
-                                                    ),
-                                                });
-                                            }
-                                        },
-                                        Err(_) => {
-                                            return Err(BevyGstError::GetPropertyError {
-                                                property: "Framerates".to_string(),
-                                                error: "Failed to get framerates: doesnt exist!"
-                                                    .to_string(),
-                                            });
-                                        }
-                                    };
-
-                                for m in frame_regex.find_iter(&value) {
-                                    let fraction_string: Vec<&str> =
-                                        m.as_str().split('/').collect();
-                                    if fraction_string.len() != 2 {
-                                        return Err(BevyGstError::GetPropertyError {
-                                            property: "Framerates".to_string(),
-                                            error: format!(
-                                                "Fraction framerate had more than one demoninator: {:?}",
-                                                fraction_string
-                                            ),
-                                        });
-                                    }
+                        let width = match capability.get::<i32>("width") {
+                            Ok(w) => w,
+                            Err(why) => {
+                                return Err(BevyGstError::GetPropertyError {
+                                    property: "Capibilities by Resolution: Width".to_string(),
+                                    error: why.to_string(),
+                                });
+                            }
+                        };
+                        let height = match capability.get::<i32>("height") {
+                            Ok(w) => w,
+                            Err(why) => {
+                                return Err(BevyGstError::GetPropertyError {
+                                    property: "Capibilities by Resolution: Height".to_string(),
+                                    error: why.to_string(),
+                                });
+                            }
+                        };
+                        let value = match capability.value_by_quark(Quark::from_str("framerate")) {
+                            Ok(v) => match v.transform::<String>() {
+                                Ok(s) => {
+                                    format!("{:?}", s)
+                                }
+                                Err(why) => {
+                                    return Err(BevyGstError::GetPropertyError {
+                                        property: "Framerates".to_string(),
+                                        error: format!(
+                                            "Failed to make framerates into string: {}",
+                                            why
+                                        ),
+                                    });
+                                }
+                            },
+                            Err(_) => {
+                                return Err(BevyGstError::GetPropertyError {
+                                    property: "Framerates".to_string(),
+                                    error: "Failed to get framerates: doesnt exist!".to_string(),
+                                });
+                            }
+                        };
 
-                                    if let Some(v) = fraction_string.get(1) {
-                                        if *v != "1" {
-                                            continue; // swallow error
-                                        }
-                                    } else {
-                                        return Err(BevyGstError::GetPropertyError { property: "Framerates".to_string(), error: "No framerate denominator? Shouldn't happen, please report!".to_string() });
-                                    }
+                        for m in frame_regex.find_iter(&value) {
+                            let fraction_string: Vec<&str> = m.as_str().split('/').collect();
+                            if fraction_string.len() != 2 {
+                                return Err(BevyGstError::GetPropertyError {
+                                    property: "Framerates".to_string(),
+                                    error: format!(
+                                        "Fraction framerate had more than one demoninator: {:?}",
+                                        fraction_string
+                                    ),
+                                });
+                            }
 
-                                    if let Some(numerator) = fraction_string.first() {
-                                        match numerator.parse::<u32>() {
-                                            Ok(fps) => fps_vec.push(fps),
-                                            Err(why) => {
-                                                return Err(BevyGstError::GetPropertyError {
-                                                    property: "Framerates".to_string(),
-                                                    error: format!(
-                                                        "Failed to parse numerator: {}",
-                                                        why
-                                                    ),
-                                                });
-                                            }
-                                        }
-                                    } else {
-                                        return Err(BevyGstError::GetPropertyError { property: "Framerates".to_string(), error: "No framerate numerator? Shouldn't happen, please report!".to_string() });
-                                    }
+                            if let Some(v) = fraction_string.get(1) {
+                                if *v != "1" {
+                                    continue; // swallow error
                                 }
-                                resolution_map
-                                    .insert(Resolution::new(width as u32, height as u32), fps_vec);
+                            } else {
+                                return Err(BevyGstError::GetPropertyError {
+                                    property: "Framerates".to_string(),
+                                    error:
+                                        "No framerate denominator? Shouldn't happen, please report!"
+                                            .to_string(),
+                                });
                             }
-                        }
-                        FrameFormat::YUYV => {
-                            if capability.name() == "video/x-raw"
-                                && capability.get::<String>("format").unwrap_or_default() == *"YUY2"
-                            {
-                                let mut fps_vec = vec![];
-
-                                let width = match capability.get::<i32>("width") {
-                                    Ok(w) => w,
-                                    Err(why) => {
-                                        return Err(BevyGstError::GetPropertyError {
-                                            property: "Capibilities by Resolution: Width"
-                                                .to_string(),
-                                            error: why.to_string(),
-                                        });
-                                    }
-                                };
-                                let height = match capability.get::<i32>("height") {
-                                    Ok(w) => w,
+
+                            if let Some(numerator) = fraction_string.first() {
+                                match numerator.parse::<u32>() {
+                                    Ok(fps) => fps_vec.push(fps),
                                     Err(why) => {
-                                        return Err(BevyGstError::GetPropertyError {
-                                            property: "Capibilities by Resolution: Height"
-                                                .to_string(),
-                                            error: why.to_string(),
-                                        });
-                                    }
-                                };
-                                let value =
-                                    match capability.value_by_quark(Quark::from_str("framerate")) {
-                                        Ok(v) => match v.transform::<String>() {
-                                            Ok(s) => {
-                                                format!("{:?}", s)
-                                            }
-                                            Err(why) => {
-                                                return Err(BevyGstError::GetPropertyError {
-                                                    property: "Framerates".to_string(),
-                                                    error: format!(
-                                                        "Failed to make framerates into string: {}",
-                                                        why
-                                                    ),
-                                                });
-                                            }
-                                        },
-                                        Err(_) => {
-                                            return Err(BevyGstError::GetPropertyError {
-                                                property: "Framerates".to_string(),
-                                                error: "Failed to get framerates: doesnt exist!"
-                                                    .to_string(),
-                                            });
-                                        }
-                                    };
-
-                                for m in frame_regex.find_iter(&value) {
-                                    let fraction_string: Vec<&str> =
-                                        m.as_str().split('/').collect();
-                                    if fraction_string.len() != 2 {
                                         return Err(BevyGstError::GetPropertyError {
                                             property: "Framerates".to_string(),
-                                            error: format!(
-                                                "Fraction framerate had more than one demoninator: {:?}",
-                                                fraction_string
-                                            ),
+                                            error: format!("Failed to parse numerator: {}", why),
                                         });
                                     }
-
-                                    if let Some(v) = fraction_string.get(1) {
-                                        if *v != "1" {
-                                            continue; // swallow error
-                                        }
-                                    } else {
-                                        return Err(BevyGstError::GetPropertyError { property: "Framerates".to_string(), error: "No framerate denominator? Shouldn't happen, please report!".to_string() });
-                                    }
-
-                                    if let Some(numerator) = fraction_string.first() {
-                                        match numerator.parse::<u32>() {
-                                            Ok(fps) => fps_vec.push(fps),
-                                            Err(why) => {
-                                                return Err(BevyGstError::GetPropertyError {
-                                                    property: "Framerates".to_string(),
-                                                    error: format!(
-                                                        "Failed to parse numerator: {}",
-                                                        why
-                                                    ),
-                                                });
-                                            }
-                                        }
-                                    } else {
-                                        return Err(BevyGstError::GetPropertyError { property: "Framerates".to_string(), error: "No framerate numerator? Shouldn't happen, please report!".to_string() });
-                                    }
                                 }
-                                resolution_map
-                                    .insert(Resolution::new(width as u32, height as u32), fps_vec);
+                            } else {
+                                return Err(BevyGstError::GetPropertyError {
+                                    property: "Framerates".to_string(),
+                                    error:
+                                        "No framerate numerator? Shouldn't happen, please report!"
+                                            .to_string(),
+                                });
                             }
                         }
-                        unsupported => {
-                            return Err(BevyGstError::NotImplementedError(format!(
-                                "Not supported frame format {unsupported:?}"
-                            )));
-                        }
+                        resolution_map
+                            .insert(Resolution::new(width as u32, height as u32), fps_vec);
                     }
                 }
             }
@@ -443,8 +768,27 @@ impl GstCamera {
     }
 }
 
-/// search device by index
-fn search_device(index: usize) -> Result<(CameraInfo, Option<Caps>), BevyGstError> {
+impl Drop for GstCamera {
+    fn drop(&mut self) {
+        // elements must be back in the null state before they are disposed
+        let _ = self.pipeline.set_state(State::Null);
+    }
+}
+
+/// list the video capture devices known to gstreamer
+pub fn query_devices() -> Result<Vec<CameraInfo>, BevyGstError> {
+    if let Err(why) = gstreamer::init() {
+        return Err(BevyGstError::InitializeError(why.to_string()));
+    }
+
+    Ok(monitor_devices()?
+        .iter()
+        .enumerate()
+        .map(|(index, device)| device_info(device, index))
+        .collect())
+}
+
+fn monitor_devices() -> Result<Vec<Device>, BevyGstError> {
     let device_monitor = DeviceMonitor::new();
 
     let video_caps = match Caps::from_str("video/x-raw") {
@@ -478,38 +822,84 @@ fn search_device(index: usize) -> Result<(CameraInfo, Option<Caps>), BevyGstErro
         });
     }
 
-    let device = match device_monitor
-        .devices()
+    let devices = device_monitor.devices().into_iter().collect();
+    device_monitor.stop();
+
+    Ok(devices)
+}
+
+/// build the camera info of a device, reading its path and stable id from the device properties
+fn device_info(device: &Device, index: usize) -> CameraInfo {
+    let properties = device.properties();
+    let property = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| properties.as_ref().and_then(|p| p.get::<String>(*key).ok()))
+    };
+
+    let mut info = CameraInfo::new(
+        &DeviceExt::display_name(device),
+        &DeviceExt::device_class(device),
+        "",
+        index,
+    );
+    let path = property(&["device.path", "api.v4l2.path"]);
+    if let Some(id) =
+        property(&["device.serial", "device.bus_path", "object.path"]).or_else(|| path.clone())
+    {
+        info = info.with_id(id);
+    }
+    if let Some(path) = path {
+        info = info.with_path(path);
+    }
+    info
+}
+
+/// search device by index, name, path or stable id
+fn search_device(index: &CameraIndex) -> Result<(CameraInfo, Option<Caps>), BevyGstError> {
+    let device = monitor_devices()?
         .iter()
         .enumerate()
-        .find(|(i, _device)| *i == index)
-    {
-        Some((_, dev)) => dev.clone(),
-        None => {
-            return Err(BevyGstError::OpenDeviceError(
-                index.to_string(),
-                "No device".to_string(),
-            ));
-        }
-    };
-    device_monitor.stop();
+        .map(|(i, device)| (device_info(device, i), device.caps()))
+        .find(|(info, _caps)| info.matches(index));
+
+    match device {
+        Some(device) => Ok(device),
+        None => Err(BevyGstError::OpenDeviceError(
+            index.to_string(),
+            "No device".to_string(),
+        )),
+    }
+}
 
-    let caps = device.caps();
-    Ok((
-        CameraInfo::new(
-            &DeviceExt::display_name(&device),
-            &DeviceExt::device_class(&device),
-            "",
-            index,
-        ),
-        caps,
-    ))
+/// the device argument of [`webcam_pipeline`] for the platform
+fn device_name(camera_info: &CameraInfo) -> String {
+    if cfg!(target_os = "linux") {
+        // the device monitor order does not have to match the /dev/video numbering
+        match camera_info.path() {
+            Some(path) => path.to_string(),
+            None => format!("/dev/video{}", camera_info.index()),
+        }
+    } else {
+        camera_info.index().to_string()
+    }
 }
 
 #[allow(clippy::too_many_lines)]
 #[allow(clippy::let_and_return)]
-fn generate_pipeline(fmt: CameraFormat, index: usize) -> Result<PipelineGenRet, BevyGstError> {
-    let appsink_pipeline = webcam_pipeline(format!("{}", index).as_str(), fmt);
+fn generate_pipeline(
+    source: &str,
+    fmt: CameraFormat,
+    camera_info: &CameraInfo,
+    frame_sync: &FrameSync,
+    options: GstCameraOptions,
+) -> Result<PipelineGenRet, BevyGstError> {
+    let index = camera_info.index();
+    let appsink_pipeline = webcam_pipeline(source, fmt, &options);
+    let GstCameraOptions {
+        orientation,
+        colorimetry,
+        ..
+    } = options;
 
     let pipeline = match gstreamer::parse::launch(&appsink_pipeline) {
         Ok(p) => p,
@@ -518,8 +908,7 @@ fn generate_pipeline(fmt: CameraFormat, index: usize) -> Result<PipelineGenRet,
                 index.to_string(),
                 format!(
                     "Failed to open pipeline with args {}: {}",
-                    webcam_pipeline(format!("{}", index).as_str(), fmt),
-                    why
+                    appsink_pipeline, why
                 ),
             ));
         }
@@ -550,10 +939,29 @@ fn generate_pipeline(fmt: CameraFormat, index: usize) -> Result<PipelineGenRet,
         }
     };
 
-    pipeline.set_state(State::Playing).unwrap();
+    // with clock sync the appsink paces the pipeline, so file sources do not outrun the queue
+    appsink.set_sync(frame_sync.mode == SyncMode::Clock);
+
+    let encoded_queue = Arc::new(Mutex::new(EncodedQueue::new()));
+    if let Some(encoded_sink) = pipeline
+        .clone()
+        .dynamic_cast::<Bin>()
+        .unwrap()
+        .by_name("encoded_sink")
+        .and_then(|sink| sink.dynamic_cast::<AppSink>().ok())
+    {
+        encoded::attach_sink(&encoded_sink, encoded_queue.clone());
+    }
+
+    if let Err(why) = pipeline.set_state(State::Playing) {
+        return Err(BevyGstError::OpenStreamError(format!(
+            "Failed to set pipeline to playing: {}",
+            why
+        )));
+    }
 
-    let image_lock = Arc::new(Mutex::new(ImageBuffer::default()));
-    let img_lck_clone = image_lock.clone();
+    let frame_queue = Arc::new(Mutex::new(FrameQueue::new(frame_sync.queue_size)));
+    let queue_clone = frame_queue.clone();
 
     appsink.set_callbacks(
         AppSinkCallbacks::builder()
@@ -597,6 +1005,11 @@ fn generate_pipeline(fmt: CameraFormat, index: usize) -> Result<PipelineGenRet,
                     return Err(FlowError::Error);
                 };
 
+                let running_time = sample
+                    .segment()
+                    .and_then(|segment| segment.downcast_ref::<ClockTime>())
+                    .and_then(|segment| segment.to_running_time(buffer.pts()));
+
                 let buffer_map = match buffer.map_readable() {
                     Ok(m) => m,
                     Err(why) => {
@@ -613,56 +1026,62 @@ fn generate_pipeline(fmt: CameraFormat, index: usize) -> Result<PipelineGenRet,
                     }
                 };
 
-                let channels = if video_info.has_alpha() { 4 } else { 3 };
-
                 let image_buffer = match video_info.format() {
-                    VideoFormat::Yuy2 => {
-                        let mut decoded_buffer = match yuyv422_to_rgb(&buffer_map, true) {
-                            Ok(buf) => buf,
-                            Err(why) => {
+                    format @ (VideoFormat::Yuy2
+                    | VideoFormat::Uyvy
+                    | VideoFormat::Yvyu
+                    | VideoFormat::Nv12
+                    | VideoFormat::P01010le
+                    | VideoFormat::I420
+                    | VideoFormat::Gray8) => {
+                        let (width, height) = (video_info.width(), video_info.height());
+                        let mut decoded_buffer = vec![0_u8; (width * height * 3) as usize];
+                        let colorimetry =
+                            colorimetry.unwrap_or_else(|| caps_colorimetry(&video_info));
+                        if let Err(why) = raw_frame(&video_info, &buffer_map).and_then(|frame| {
+                            convert(
+                                &frame,
+                                width,
+                                height,
+                                &mut decoded_buffer,
+                                PixelFormat::Rgb8,
+                                colorimetry,
+                            )
+                        }) {
+                            element_error!(
+                                appsink,
+                                ResourceError::Failed,
+                                (
+                                    "{}",
+                                    format!("Failed to convert {format} frame: {why}").as_str()
+                                )
+                            );
+
+                            return Err(FlowError::Error);
+                        }
+
+                        match ImageBuffer::from_vec(width, height, decoded_buffer) {
+                            Some(image) => image,
+                            None => {
                                 element_error!(
                                     appsink,
                                     ResourceError::Failed,
-                                    (
-                                        "{}",
-                                        format!("Failed to make yuy2 into rgb888: {}", why)
-                                            .as_str()
-                                    )
+                                    ("Failed to make rgb buffer into imagebuffer")
                                 );
 
                                 return Err(FlowError::Error);
                             }
-                        };
-
-                        decoded_buffer.resize(
-                            (video_info.width() * video_info.height() * channels) as usize,
-                            0_u8,
-                        );
-
-                        let image = if let Some(i) = ImageBuffer::from_vec(
-                            video_info.width(),
-                            video_info.height(),
-                            decoded_buffer,
-                        ) {
-                            let rgb: ImageBuffer<Rgb<u8>, Vec<u8>> = i;
-                            rgb
-                        } else {
-                            element_error!(
-                                appsink,
-                                ResourceError::Failed,
-                                ("Failed to make rgb buffer into imagebuffer")
-                            );
-
-                            return Err(FlowError::Error);
-                        };
-                        image
+                        }
                     }
                     VideoFormat::Rgb => {
-                        let mut decoded_buffer = buffer_map.as_slice().to_vec();
-                        decoded_buffer.resize(
-                            (video_info.width() * video_info.height() * channels) as usize,
-                            0_u8,
-                        );
+                        // rows are padded to 4 bytes
+                        let row_len = video_info.width() as usize * 3;
+                        let stride = video_info.stride()[0] as usize;
+                        let decoded_buffer = buffer_map
+                            .chunks(stride)
+                            .take(video_info.height() as usize)
+                            .flat_map(|row| row[..row_len].iter().copied())
+                            .collect();
                         let image = if let Some(i) = ImageBuffer::from_vec(
                             video_info.width(),
                             video_info.height(),
@@ -682,7 +1101,9 @@ fn generate_pipeline(fmt: CameraFormat, index: usize) -> Result<PipelineGenRet,
                         image
                     }
                     // MJPEG
+                    #[cfg(feature = "cpu-mjpeg")]
                     VideoFormat::Encoded => {
+                        let channels = if video_info.has_alpha() { 4 } else { 3 };
                         let mut decoded_buffer = match mjpeg_to_rgb24(&buffer_map) {
                             Ok(buf) => buf,
                             Err(why) => {
@@ -733,115 +1154,196 @@ fn generate_pipeline(fmt: CameraFormat, index: usize) -> Result<PipelineGenRet,
                     }
                 };
 
-                if let Ok(mut img) = img_lck_clone.lock() {
-                    *img = image_buffer;
+                let image_buffer = match video_info.format() {
+                    VideoFormat::Encoded | VideoFormat::P01010le => orientation.apply(image_buffer),
+                    _ => image_buffer,
+                };
+
+                if let Ok(mut queue) = queue_clone.lock() {
+                    queue.push(QueuedFrame {
+                        image: image_buffer,
+                        running_time,
+                    });
                 }
 
                 Ok(FlowSuccess::Ok)
             })
             .build(),
     );
-    Ok((pipeline, appsink, image_lock))
+    Ok((pipeline, appsink, frame_queue, encoded_queue))
 }
 
-#[cfg(target_os = "macos")]
-fn webcam_pipeline(device: &str, camera_format: CameraFormat) -> String {
-    match camera_format.format() {
-        FrameFormat::MJPEG => {
-            format!(
-                "autovideosrc location=/dev/video{} ! image/jpeg,width={},height={},framerate={}/1 ! appsink name=appsink async=false sync=false",
-                device,
-                camera_format.width(),
-                camera_format.height(),
-                camera_format.frame_rate()
-            )
-        }
-        FrameFormat::YUYV => {
-            format!(
-                "autovideosrc location=/dev/video{} ! video/x-raw,format=YUY2,width={},height={},framerate={}/1 ! appsink name=appsink async=false sync=false",
-                device,
-                camera_format.width(),
-                camera_format.height(),
-                camera_format.frame_rate()
-            )
-        }
-        FrameFormat::GRAY => {
-            format!(
-                "autovideosrc location=/dev/video{} ! video/x-raw,format=GRAY8,width={},height={},framerate={}/1 ! appsink name=appsink async=false sync=false",
-                device,
-                camera_format.width(),
-                camera_format.height(),
-                camera_format.frame_rate()
-            )
+/// the planes of a raw frame mapped from a buffer
+fn raw_frame<'a>(video_info: &VideoInfo, data: &'a [u8]) -> Result<RawFrame<'a>, BevyGstError> {
+    let plane = |i: usize| {
+        Plane::new(
+            data.get(video_info.offset()[i]..).unwrap_or_default(),
+            video_info.stride()[i] as usize,
+        )
+    };
+    Ok(match video_info.format() {
+        VideoFormat::Yuy2 => RawFrame::Yuyv(plane(0)),
+        VideoFormat::Uyvy => RawFrame::Uyvy(plane(0)),
+        VideoFormat::Yvyu => RawFrame::Yvyu(plane(0)),
+        VideoFormat::Nv12 => RawFrame::Nv12 {
+            y: plane(0),
+            uv: plane(1),
+        },
+        VideoFormat::P01010le => RawFrame::P010 {
+            y: plane(0),
+            uv: plane(1),
+        },
+        VideoFormat::I420 => RawFrame::I420 {
+            y: plane(0),
+            u: plane(1),
+            v: plane(2),
+        },
+        VideoFormat::Gray8 => RawFrame::Gray(plane(0)),
+        format => {
+            return Err(BevyGstError::ConvertFrameError(format!(
+                "{format} has no CPU converter"
+            )));
         }
-        _ => "unsupproted! if you see this, switch to something else!".to_string(),
+    })
+}
+
+/// the matrix and range announced by the caps
+///
+/// An unknown matrix is guessed from the height like gstreamer does, BT.709 above 576 rows.
+fn caps_colorimetry(video_info: &VideoInfo) -> Colorimetry {
+    let colorimetry = video_info.colorimetry();
+    let matrix = match colorimetry.matrix() {
+        VideoColorMatrix::Bt601 | VideoColorMatrix::Fcc => ColorMatrix::Bt601,
+        VideoColorMatrix::Bt709 | VideoColorMatrix::Smpte240m => ColorMatrix::Bt709,
+        VideoColorMatrix::Bt2020 => ColorMatrix::Bt2020,
+        _ if video_info.height() > 576 => ColorMatrix::Bt709,
+        _ => ColorMatrix::Bt601,
+    };
+    let range = match colorimetry.range() {
+        VideoColorRange::Range0_255 => ColorRange::Full,
+        _ => ColorRange::Limited,
+    };
+    Colorimetry::new(matrix, range)
+}
+
+/// the part of the pipeline producing frames in `fmt`, from a custom source or the device
+fn pipeline_source(
+    custom_source: Option<&str>,
+    camera_info: &CameraInfo,
+    fmt: CameraFormat,
+) -> String {
+    match custom_source {
+        Some(source) => format!("{source} ! {}", format_caps(fmt)),
+        None => webcam_source(&device_name(camera_info), fmt),
     }
 }
 
-#[cfg(target_os = "linux")]
-fn webcam_pipeline(device: &str, camera_format: CameraFormat) -> String {
-    match camera_format.format() {
-        FrameFormat::MJPEG => {
-            format!(
-                "v4l2src device=/dev/video{} ! image/jpeg, width={},height={},framerate={}/1 ! appsink name=appsink async=false sync=false",
-                device,
-                camera_format.width(),
-                camera_format.height(),
-                camera_format.frame_rate()
-            )
-        }
-        FrameFormat::YUYV => {
-            format!(
-                "v4l2src device=/dev/video{} ! video/x-raw,format=YUY2,width={},height={},framerate={}/1 ! appsink name=appsink async=false sync=false",
-                device,
-                camera_format.width(),
-                camera_format.height(),
-                camera_format.frame_rate()
-            )
-        }
-        FrameFormat::GRAY => {
-            format!(
-                "v4l2src device=/dev/video{} ! video/x-raw,format=GRAY8,width={},height={},framerate={}/1 ! appsink name=appsink async=false sync=false",
-                device,
-                camera_format.width(),
-                camera_format.height(),
-                camera_format.frame_rate()
-            )
-        }
-        _ => "unsupported! if you see this, switch to something else!".to_string(),
+/// the media type and raw video format of `format` in caps
+fn frame_format_caps(format: FrameFormat) -> (&'static str, Option<&'static str>) {
+    match format {
+        FrameFormat::MJPEG => ("image/jpeg", None),
+        FrameFormat::YUYV => ("video/x-raw", Some("YUY2")),
+        FrameFormat::UYVY => ("video/x-raw", Some("UYVY")),
+        FrameFormat::YVYU => ("video/x-raw", Some("YVYU")),
+        FrameFormat::NV12 => ("video/x-raw", Some("NV12")),
+        FrameFormat::P010 => ("video/x-raw", Some("P010_10LE")),
+        FrameFormat::GRAY => ("video/x-raw", Some("GRAY8")),
+        FrameFormat::RAWRGB => ("video/x-raw", Some("RGB")),
+        FrameFormat::H264 => ("video/x-h264", None),
+        FrameFormat::H265 => ("video/x-h265", None),
+        FrameFormat::VP8 => ("video/x-vp8", None),
+        FrameFormat::VP9 => ("video/x-vp9", None),
     }
 }
 
-#[cfg(target_os = "windows")]
-fn webcam_pipeline(device: &str, camera_format: CameraFormat) -> String {
-    match camera_format.format() {
-        FrameFormat::MJPEG => {
-            format!(
-                "mfvideosrc   device_index={} ! image/jpeg, width={},height={},framerate={}/1 ! appsink name=appsink async=false sync=false",
-                device,
-                camera_format.width(),
-                camera_format.height(),
-                camera_format.frame_rate()
-            )
-        }
-        FrameFormat::YUYV => {
-            format!(
-                "mfvideosrc   device_index={} ! video/x-raw,format=YUY2,width={},height={},framerate={}/1 ! appsink name=appsink async=false sync=false",
-                device,
-                camera_format.width(),
-                camera_format.height(),
-                camera_format.frame_rate()
-            )
-        }
-        FrameFormat::GRAY => {
-            format!(
-                "mfvideosrc   device_index={} ! video/x-raw,format=GRAY8,width={},height={},framerate={}/1 ! appsink name=appsink async=false sync=false",
-                device,
-                camera_format.width(),
-                camera_format.height(),
-                camera_format.frame_rate()
-            )
-        }
-        _ => "unsupproted! if you see this, switch to something else!".to_string(),
+/// whether a caps structure describes frames in `format`
+fn caps_match(structure: &StructureRef, format: FrameFormat) -> bool {
+    let (media, raw_format) = frame_format_caps(format);
+    structure.name() == media
+        && (raw_format.is_none() || structure.get::<&str>("format").ok() == raw_format)
+}
+
+/// caps selecting `fmt`
+pub(crate) fn format_caps(fmt: CameraFormat) -> String {
+    let media = match frame_format_caps(fmt.format()) {
+        (media, Some(format)) => format!("{media},format={format}"),
+        (media, None) => media.to_string(),
+    };
+    format!(
+        "{},width={},height={},framerate={}/1",
+        media,
+        fmt.width(),
+        fmt.height(),
+        fmt.frame_rate()
+    )
+}
+
+/// build the capture pipeline, with `videoflip` applying the orientation to raw frames
+///
+/// Compressed streams are decoded to RGB before the flip, with [`EncodedPassthrough`] a `tee`
+/// hands the samples to the `encoded_sink` too.
+fn webcam_pipeline(
+    source: &str,
+    camera_format: CameraFormat,
+    options: &GstCameraOptions,
+) -> String {
+    let flip = if options.orientation.is_identity() {
+        String::new()
+    } else {
+        format!(
+            " ! videoflip video-direction={}",
+            options.orientation.video_direction()
+        )
+    };
+    let flip = match camera_format.format() {
+        // videoflip does not take 10 bit frames, they are turned after conversion in the appsink
+        FrameFormat::P010 => String::new(),
+        FrameFormat::MJPEG => match options.mjpeg_decoding.elements() {
+            Some(decode) => format!(" ! {decode}{flip}"),
+            // encoded frames are turned after decoding in the appsink
+            None => String::new(),
+        },
+        format => match decode::stream_decoder(format) {
+            Some(decode) => format!(" ! {decode}{flip}"),
+            None => flip,
+        },
+    };
+    let frame_sink = "appsink name=appsink async=false sync=false";
+    let parsed = || format!("{source}{}", encoded::parser(camera_format.format()));
+    match options.encoded_passthrough {
+        EncodedPassthrough::Off => format!("{source}{flip} ! {frame_sink}"),
+        EncodedPassthrough::Alongside => format!(
+            "{} ! tee name=encoded ! queue{flip} ! {frame_sink} encoded. ! queue ! {ENCODED_SINK}",
+            parsed()
+        ),
+        // the frame appsink stays unlinked, the camera always has one
+        EncodedPassthrough::Only => format!("{} ! {ENCODED_SINK} {frame_sink}", parsed()),
     }
 }
+
+#[cfg(target_os = "macos")]
+fn webcam_source(device: &str, camera_format: CameraFormat) -> String {
+    format!(
+        "autovideosrc name=source location=/dev/video{} ! {}",
+        device,
+        format_caps(camera_format)
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn webcam_source(device: &str, camera_format: CameraFormat) -> String {
+    format!(
+        "v4l2src name=source device={} ! {}",
+        device,
+        format_caps(camera_format)
+    )
+}
+
+#[cfg(target_os = "windows")]
+fn webcam_source(device: &str, camera_format: CameraFormat) -> String {
+    format!(
+        "mfvideosrc name=source device_index={} ! {}",
+        device,
+        format_caps(camera_format)
+    )
+}
@@ -1,23 +1,35 @@
-use crate::camera::{BackgroundImageMarker, GstCamera};
-use bevy::asset::RenderAssetUsages;
-use bevy::image::TextureFormatPixelInfo;
+use bevy::asset::weak_handle;
+use bevy::core_pipeline::core_3d::CORE_3D_DEPTH_FORMAT;
+use bevy::ecs::query::QueryItem;
 use bevy::prelude::*;
-use bevy::render::extract_resource::ExtractResource;
-use bevy::render::render_graph::{Node, RenderLabel, RenderSubGraph};
-use bevy::render::render_graph::{NodeRunError, RenderGraphContext, SlotInfo};
+use bevy::render::Extract;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_graph::{NodeRunError, RenderGraphContext, RenderLabel, ViewNode};
 use bevy::render::render_resource::{
-    AddressMode, BindGroup, BindGroupEntries, BindGroupLayoutEntry, BindingType, BlendComponent,
-    BlendState, Buffer, BufferAddress, BufferInitDescriptor, BufferUsages, ColorTargetState,
-    ColorWrites, Extent3d, Face, FilterMode, FrontFace, IndexFormat, MultisampleState,
-    PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RawFragmentState,
-    RawRenderPipelineDescriptor, RawVertexBufferLayout, RawVertexState, RenderPassDescriptor,
-    RenderPipeline, SamplerBindingType, SamplerDescriptor, ShaderModuleDescriptor, ShaderSource,
-    ShaderStages, TexelCopyBufferLayout, TextureDescriptor, TextureDimension, TextureFormat,
-    TextureSampleType, TextureUsages, TextureViewDescriptor, TextureViewDimension, VertexAttribute,
-    VertexFormat, VertexStepMode,
+    BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntry, BindingType,
+    BlendComponent, BlendFactor, BlendOperation, BlendState, Buffer, BufferBindingType,
+    BufferInitDescriptor, BufferUsages, CachedRenderPipelineId, ColorTargetState, ColorWrites,
+    CompareFunction, DepthBiasState, DepthStencilState, DynamicUniformBuffer, Face, FragmentState,
+    FrontFace, IndexFormat, MultisampleState, PipelineCache, PolygonMode, PrimitiveState,
+    PrimitiveTopology, RenderPassDescriptor, RenderPipelineDescriptor, SamplerBindingType,
+    ShaderStages, ShaderType, SpecializedRenderPipeline, SpecializedRenderPipelines, StencilState,
+    StoreOp, TextureFormat, TextureSampleType, TextureViewDimension, VertexBufferLayout,
+    VertexFormat, VertexState, VertexStepMode,
 };
 use bevy::render::renderer::{RenderContext, RenderDevice, RenderQueue};
-use bevy::render::view::{ExtractedView, ViewTarget};
+use bevy::render::sync_world::RenderEntity;
+use bevy::render::texture::{FallbackImage, GpuImage};
+use bevy::render::view::{ExtractedView, ViewDepthTexture, ViewTarget};
+
+use crate::camera::chroma_key::{ChromaKey, ChromaKeyUniform};
+use crate::camera::grading::{ColorGradingUniform, GstColorGrading};
+use crate::camera::output::GstCameraImage;
+
+pub(crate) const BACKGROUND_SHADER_HANDLE: Handle<Shader> =
+    weak_handle!("5f0b7c1e-2a43-4d8e-9b61-3c7d0e9a4f21");
+/// bindings and helpers of the background pass, `bevy_gstreamer::background` in shaders
+pub(crate) const BACKGROUND_IMPORT_SHADER_HANDLE: Handle<Shader> =
+    weak_handle!("e2b97d04-1c6a-4f53-8a0d-95b3f7c6e218");
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -27,28 +39,387 @@ struct Vertex {
 }
 
 impl Vertex {
-    fn desc<'a>() -> RawVertexBufferLayout<'a> {
-        RawVertexBufferLayout {
-            array_stride: size_of::<Vertex>() as BufferAddress,
-            step_mode: VertexStepMode::Vertex,
-            attributes: &[
-                VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: VertexFormat::Float32x3,
-                },
-                VertexAttribute {
-                    offset: size_of::<[f32; 3]>() as BufferAddress,
-                    shader_location: 1,
-                    format: VertexFormat::Float32x2,
-                },
-            ],
+    fn desc() -> VertexBufferLayout {
+        VertexBufferLayout::from_vertex_formats(
+            VertexStepMode::Vertex,
+            [VertexFormat::Float32x3, VertexFormat::Float32x2],
+        )
+    }
+}
+
+/// How the camera frame is fitted into a view, add it to a bevy camera to change it
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+#[reflect(Component, Default)]
+pub enum BackgroundFit {
+    /// stretch the frame over the whole view, ignoring its aspect ratio
+    #[default]
+    Stretch,
+    /// show the whole frame, filling the bars around it with the color
+    Contain(Color),
+    /// fill the whole view, cropping the frame
+    Cover,
+    /// stretch a sub-rectangle of the frame, in normalized texture coordinates, over the view
+    UvRect(Rect),
+}
+
+impl BackgroundFit {
+    /// compute the mapping from view to frame texture coordinates
+    pub(crate) fn uniform(&self, frame_size: Vec2, view_size: Vec2) -> BackgroundUniform {
+        let mut uniform = BackgroundUniform {
+            uv_offset: Vec2::ZERO,
+            uv_scale: Vec2::ONE,
+            clear_color: Vec4::ZERO,
+            opacity: 1.0,
+            time: 0.0,
+            chroma_key: ChromaKey::uniform(None),
+            grading: GstColorGrading::uniform(None, false),
+            params: [Vec4::ZERO; 4],
+        };
+        if frame_size.min_element() <= 0.0 || view_size.min_element() <= 0.0 {
+            return uniform;
+        }
+
+        // > 1 when the view is wider than the frame
+        let ratio = (view_size.x / view_size.y) / (frame_size.x / frame_size.y);
+        match self {
+            BackgroundFit::Stretch => {}
+            BackgroundFit::Contain(color) => {
+                uniform.uv_scale = if ratio > 1.0 {
+                    Vec2::new(ratio, 1.0)
+                } else {
+                    Vec2::new(1.0, 1.0 / ratio)
+                };
+                uniform.clear_color = color.to_linear().to_vec4();
+            }
+            BackgroundFit::Cover => {
+                uniform.uv_scale = if ratio > 1.0 {
+                    Vec2::new(1.0, 1.0 / ratio)
+                } else {
+                    Vec2::new(ratio, 1.0)
+                };
+            }
+            BackgroundFit::UvRect(rect) => {
+                uniform.uv_scale = rect.size();
+                uniform.uv_offset = rect.min;
+                return uniform;
+            }
+        }
+        // keep the frame centered
+        uniform.uv_offset = (Vec2::ONE - uniform.uv_scale) / 2.0;
+        uniform
+    }
+}
+
+#[derive(Clone, Copy, Debug, ShaderType)]
+pub struct BackgroundUniform {
+    pub uv_offset: Vec2,
+    pub uv_scale: Vec2,
+    /// color outside the frame when letterboxing
+    pub clear_color: Vec4,
+    pub opacity: f32,
+    /// before the structs, a member after `grading` would break the uniform layout
+    pub time: f32,
+    pub chroma_key: ChromaKeyUniform,
+    pub grading: ColorGradingUniform,
+    pub params: [Vec4; 4],
+}
+
+/// Replace the fragment shader drawing the background of this bevy camera
+///
+/// The shader defines `@fragment fn fragment(in: VertexOutput) -> @location(0) vec4<f32>` and
+/// imports the rest from `bevy_gstreamer::background`:
+///
+/// ```wgsl
+/// #import bevy_gstreamer::background::{VertexOutput, background, composite, frame_uv, sample_frame}
+///
+/// @fragment
+/// fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+///     // pixelate, with the cell count in the first parameter
+///     let cells = background.params[0].x;
+///     let uv = (floor(frame_uv(in.tex_coords) * cells) + 0.5) / cells;
+///     return composite(sample_frame(uv));
+/// }
+/// ```
+///
+/// `background.time` holds the seconds since startup and `background.params` these parameters.
+#[derive(Component, Clone, Debug, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct BackgroundShader {
+    pub shader: Handle<Shader>,
+    pub params: [Vec4; 4],
+}
+
+impl BackgroundShader {
+    pub fn new(shader: Handle<Shader>) -> Self {
+        Self {
+            shader,
+            params: [Vec4::ZERO; 4],
+        }
+    }
+}
+
+/// How the background is composited with the rendered scene, add it to a bevy camera to change it
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+#[reflect(Component, Default)]
+pub struct BackgroundComposite {
+    pub order: BackgroundOrder,
+    pub blend: BackgroundBlend,
+    /// multiplies the frame alpha
+    pub opacity: f32,
+}
+
+impl Default for BackgroundComposite {
+    fn default() -> Self {
+        Self {
+            order: BackgroundOrder::Behind,
+            blend: BackgroundBlend::Replace,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl BackgroundComposite {
+    /// draw the frame over the scene with the given opacity
+    pub fn overlay(opacity: f32) -> Self {
+        Self {
+            order: BackgroundOrder::Overlay,
+            blend: BackgroundBlend::Alpha,
+            opacity,
+        }
+    }
+}
+
+/// When the background is drawn relative to the main pass
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, Reflect)]
+pub enum BackgroundOrder {
+    /// before the opaque pass at far depth, so the scene renders over the frame
+    #[default]
+    Behind,
+    /// after the transparent pass, over the whole scene
+    Overlay,
+}
+
+/// How the frame is blended with what is already in the view
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, Reflect)]
+pub enum BackgroundBlend {
+    /// overwrite the view
+    #[default]
+    Replace,
+    Alpha,
+    Add,
+    Multiply,
+    Screen,
+}
+
+impl BackgroundBlend {
+    fn blend_state(&self) -> BlendState {
+        // the shader outputs premultiplied alpha, or the multiplier for `Multiply`
+        let color = match self {
+            BackgroundBlend::Replace => return BlendState::REPLACE,
+            BackgroundBlend::Alpha => return BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BackgroundBlend::Add => BlendComponent {
+                src_factor: BlendFactor::One,
+                dst_factor: BlendFactor::One,
+                operation: BlendOperation::Add,
+            },
+            BackgroundBlend::Multiply => BlendComponent {
+                src_factor: BlendFactor::Dst,
+                dst_factor: BlendFactor::Zero,
+                operation: BlendOperation::Add,
+            },
+            BackgroundBlend::Screen => BlendComponent {
+                src_factor: BlendFactor::One,
+                dst_factor: BlendFactor::OneMinusSrc,
+                operation: BlendOperation::Add,
+            },
+        };
+        BlendState {
+            color,
+            // keep the alpha of the view
+            alpha: BlendComponent {
+                src_factor: BlendFactor::Zero,
+                dst_factor: BlendFactor::One,
+                operation: BlendOperation::Add,
+            },
         }
     }
 }
 
-#[derive(Deref, DerefMut, Default, Resource, ExtractResource, Clone)]
-pub struct BackgroundImage(pub Image);
+/// Draw the frames of a [`GstCamera`](crate::camera::GstCamera) behind this bevy camera
+///
+/// `source` is the entity holding the `GstCamera`, views without this component draw no background.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+pub struct CameraBackground {
+    pub source: Entity,
+}
+
+/// The camera image, fit and compositing of a view, extracted from [`CameraBackground`]
+#[derive(Component)]
+pub(crate) struct ExtractedBackground {
+    image: AssetId<Image>,
+    fit: BackgroundFit,
+    composite: BackgroundComposite,
+    chroma_key: Option<ChromaKey>,
+    grading: Option<GstColorGrading>,
+    shader: Option<BackgroundShader>,
+    time: f32,
+}
+
+/// Per view background pipeline, specialized by [`BackgroundPipelineKey`]
+#[derive(Component)]
+pub(crate) struct ViewBackgroundPipeline(CachedRenderPipelineId);
+
+/// Per view bind group of the frame texture and offset into [`BackgroundUniforms`]
+#[derive(Component)]
+pub(crate) struct ViewBackground {
+    bind_group: BindGroup,
+    uniform_offset: u32,
+}
+
+#[derive(Resource, Default)]
+pub(crate) struct BackgroundUniforms {
+    buffer: DynamicUniformBuffer<BackgroundUniform>,
+    bind_group: Option<BindGroup>,
+}
+
+type BackgroundCameraQuery = (
+    &'static RenderEntity,
+    &'static Camera,
+    &'static CameraBackground,
+    Option<&'static BackgroundFit>,
+    Option<&'static BackgroundComposite>,
+    Option<&'static ChromaKey>,
+    Option<&'static BackgroundShader>,
+);
+
+pub(crate) fn extract_camera_backgrounds(
+    mut commands: Commands,
+    mut previous: Local<Vec<Entity>>,
+    cameras: Extract<Query<BackgroundCameraQuery>>,
+    images: Extract<Query<(&GstCameraImage, Option<&GstColorGrading>)>>,
+    time: Extract<Res<Time>>,
+) {
+    // render entities outlive a frame, so the backgrounds of the last frame are removed first
+    for entity in previous.drain(..) {
+        if let Ok(mut entity) = commands.get_entity(entity) {
+            entity.remove::<(ExtractedBackground, ViewBackgroundPipeline, ViewBackground)>();
+        }
+    }
+
+    for (render_entity, camera, background, fit, composite, chroma_key, shader) in &cameras {
+        if !camera.is_active {
+            continue;
+        }
+        let Ok((image, grading)) = images.get(background.source) else {
+            continue;
+        };
+        commands
+            .entity(render_entity.id())
+            .insert(ExtractedBackground {
+                image: image.id(),
+                fit: fit.copied().unwrap_or_default(),
+                composite: composite.copied().unwrap_or_default(),
+                chroma_key: chroma_key.copied(),
+                grading: grading.cloned(),
+                shader: shader.cloned(),
+                time: time.elapsed_secs_wrapped(),
+            });
+        previous.push(render_entity.id());
+    }
+}
+
+pub(crate) fn prepare_background_pipelines(
+    mut commands: Commands,
+    views: Query<(
+        Entity,
+        &ViewTarget,
+        &Msaa,
+        &ExtractedBackground,
+        Has<ViewDepthTexture>,
+    )>,
+    pipeline: Res<BackgroundPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<BackgroundPipeline>>,
+) {
+    for (entity, target, msaa, background, has_depth) in &views {
+        let composite = background.composite;
+        let key = BackgroundPipelineKey {
+            samples: msaa.samples(),
+            hdr: target.is_hdr(),
+            format: target.main_texture_format(),
+            blend: composite.blend,
+            depth: has_depth && composite.order == BackgroundOrder::Behind,
+            shader: background
+                .shader
+                .as_ref()
+                .map(|shader| shader.shader.clone()),
+        };
+        let id = pipelines.specialize(&pipeline_cache, &pipeline, key);
+        commands.entity(entity).insert(ViewBackgroundPipeline(id));
+    }
+}
+
+pub(crate) fn prepare_backgrounds(
+    mut commands: Commands,
+    mut uniforms: ResMut<BackgroundUniforms>,
+    views: Query<(Entity, &ExtractedView, &ExtractedBackground)>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    fallback_image: Res<FallbackImage>,
+    pipeline: Res<BackgroundPipeline>,
+    (device, queue): (Res<RenderDevice>, Res<RenderQueue>),
+) {
+    let uniforms = uniforms.as_mut();
+    uniforms.buffer.clear();
+    for (entity, view, background) in &views {
+        let Some(image) = gpu_images.get(background.image) else {
+            continue;
+        };
+        let frame_size = Vec2::new(image.size.width as f32, image.size.height as f32);
+        let view_size = view.viewport.zw().as_vec2();
+        let mut uniform = background.fit.uniform(frame_size, view_size);
+        uniform.opacity = background.composite.opacity;
+        uniform.chroma_key = ChromaKey::uniform(background.chroma_key.as_ref());
+        uniform.time = background.time;
+        if let Some(shader) = &background.shader {
+            uniform.params = shader.params;
+        }
+
+        // the lut is bound even when unused, layouts are fixed
+        let lut = background
+            .grading
+            .as_ref()
+            .and_then(|grading| grading.lut.as_ref())
+            .and_then(|lut| gpu_images.get(lut));
+        uniform.grading = GstColorGrading::uniform(background.grading.as_ref(), lut.is_some());
+        let lut = lut.unwrap_or(&fallback_image.d3);
+
+        let uniform_offset = uniforms.buffer.push(&uniform);
+        let bind_group = device.create_bind_group(
+            Some("background_texture_bind_group"),
+            &pipeline.texture_layout,
+            &BindGroupEntries::sequential((
+                &image.texture_view,
+                &image.sampler,
+                &lut.texture_view,
+                &lut.sampler,
+            )),
+        );
+        commands.entity(entity).insert(ViewBackground {
+            bind_group,
+            uniform_offset,
+        });
+    }
+    uniforms.buffer.write_buffer(&device, &queue);
+
+    uniforms.bind_group = uniforms.buffer.binding().map(|binding| {
+        device.create_bind_group(
+            Some("background_uniform_bind_group"),
+            &pipeline.uniform_layout,
+            &BindGroupEntries::single(binding),
+        )
+    });
+}
 
 const VERTICES: &[Vertex] = &[
     Vertex {
@@ -74,29 +445,36 @@ const INDICES: &[u16] = &[0, 1, 2, 2, 1, 3];
 #[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
 pub(crate) struct BackgroundNodeLabel;
 
-#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderSubGraph)]
-pub struct BackgroundGraph;
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+pub(crate) struct BackgroundOverlayNodeLabel;
 
 #[derive(Resource)]
 pub struct BackgroundPipeline {
-    render_pipeline: RenderPipeline,
+    texture_layout: BindGroupLayout,
+    uniform_layout: BindGroupLayout,
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+}
+
+/// What the background pipeline is specialized on, taken from each view
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct BackgroundPipelineKey {
+    pub samples: u32,
+    pub hdr: bool,
+    /// format of the view main texture, `Rgba16Float` for hdr views
+    pub format: TextureFormat,
+    pub blend: BackgroundBlend,
+    /// test against the view depth, the frame is drawn at far depth
+    pub depth: bool,
+    /// fragment shader of a [`BackgroundShader`]
+    pub shader: Option<Handle<Shader>>,
 }
 
 impl FromWorld for BackgroundPipeline {
     fn from_world(world: &mut World) -> Self {
-        let mut query = world.query_filtered::<&Msaa, With<Camera>>();
-        let msaa = match query.single(world) {
-            Ok(m) => *m,
-            Err(_) => Msaa::Sample4,
-        };
         let device = world.resource::<RenderDevice>();
 
-        let shader = device.create_and_validate_shader_module(ShaderModuleDescriptor {
-            label: Some("Webcam Shader"),
-            source: ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
-        });
-
-        let texture_bind_group_layout = device.create_bind_group_layout(
+        let texture_layout = device.create_bind_group_layout(
             "webcam_bind_group_layout",
             &[
                 BindGroupLayoutEntry {
@@ -115,34 +493,89 @@ impl FromWorld for BackgroundPipeline {
                     ty: BindingType::Sampler(SamplerBindingType::Filtering),
                     count: None,
                 },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
             ],
         );
 
-        let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: Some("Webcam Render Pipeline Layout"),
-            bind_group_layouts: &[&texture_bind_group_layout],
-            push_constant_ranges: &[],
+        let uniform_layout = device.create_bind_group_layout(
+            "background_uniform_bind_group_layout",
+            &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: Some(BackgroundUniform::min_size()),
+                },
+                count: None,
+            }],
+        );
+
+        let vertex_buffer = device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::cast_slice(VERTICES),
+            usage: BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("Index Buffer"),
+            contents: bytemuck::cast_slice(INDICES),
+            usage: BufferUsages::INDEX,
         });
 
-        let render_pipeline = device.create_render_pipeline(&RawRenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: RawVertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                compilation_options: Default::default(),
-                buffers: &[Vertex::desc()],
+        Self {
+            texture_layout,
+            uniform_layout,
+            vertex_buffer,
+            index_buffer,
+        }
+    }
+}
+
+impl SpecializedRenderPipeline for BackgroundPipeline {
+    type Key = BackgroundPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let mut shader_defs = vec![];
+        if key.blend == BackgroundBlend::Multiply {
+            shader_defs.push("BLEND_MULTIPLY".into());
+        }
+
+        RenderPipelineDescriptor {
+            label: Some("background_pipeline".into()),
+            layout: vec![self.texture_layout.clone(), self.uniform_layout.clone()],
+            push_constant_ranges: vec![],
+            vertex: VertexState {
+                shader: BACKGROUND_SHADER_HANDLE,
+                shader_defs: vec![],
+                entry_point: "vs_main".into(),
+                buffers: vec![Vertex::desc()],
             },
-            fragment: Some(RawFragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                compilation_options: Default::default(),
-                targets: &[Some(ColorTargetState {
-                    format: TextureFormat::bevy_default(),
-                    blend: Some(BlendState {
-                        color: BlendComponent::REPLACE,
-                        alpha: BlendComponent::REPLACE,
-                    }),
+            fragment: Some(FragmentState {
+                shader: key.shader.clone().unwrap_or(BACKGROUND_SHADER_HANDLE),
+                shader_defs,
+                entry_point: if key.shader.is_some() {
+                    "fragment".into()
+                } else {
+                    "fs_main".into()
+                },
+                targets: vec![Some(ColorTargetState {
+                    format: key.format,
+                    blend: Some(key.blend.blend_state()),
                     write_mask: ColorWrites::ALL,
                 })],
             }),
@@ -151,213 +584,128 @@ impl FromWorld for BackgroundPipeline {
                 strip_index_format: None,
                 front_face: FrontFace::Ccw,
                 cull_mode: Some(Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
                 polygon_mode: PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
                 unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
                 conservative: false,
             },
-            depth_stencil: None,
+            // only draw where nothing was rendered yet, reverse z puts far at 0
+            depth_stencil: key.depth.then(|| DepthStencilState {
+                format: CORE_3D_DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::GreaterEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
             multisample: MultisampleState {
-                count: msaa.samples(),
+                count: key.samples,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
-            // If the pipeline will be used with a multiview render pass, this
-            // indicates how many array layers the attachments will have.
-            multiview: None,
-            cache: None,
-        });
-
-        Self { render_pipeline }
+            zero_initialize_workgroup_memory: false,
+        }
     }
 }
 
-pub struct BackgroundPassDriverNode;
+/// Draws the backgrounds ordered [`BackgroundOrder::Behind`]
+#[derive(Default)]
+pub(crate) struct BackgroundNode;
+
+/// Draws the backgrounds ordered [`BackgroundOrder::Overlay`]
+#[derive(Default)]
+pub(crate) struct BackgroundOverlayNode;
+
+type BackgroundViewQuery = (
+    &'static ViewTarget,
+    Option<&'static ViewDepthTexture>,
+    &'static ExtractedBackground,
+    &'static ViewBackgroundPipeline,
+    &'static ViewBackground,
+);
+
+impl ViewNode for BackgroundNode {
+    type ViewQuery = BackgroundViewQuery;
 
-impl Node for BackgroundPassDriverNode {
     fn run(
         &self,
-        graph: &mut RenderGraphContext,
-        _render_context: &mut RenderContext,
-        _world: &World,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        view: QueryItem<Self::ViewQuery>,
+        world: &World,
     ) -> Result<(), NodeRunError> {
-        graph.run_sub_graph(BackgroundGraph, vec![], Some(graph.view_entity()))?;
-
+        draw_background(BackgroundOrder::Behind, render_context, view, world);
         Ok(())
     }
 }
 
-pub struct BackgroundNode {
-    query: QueryState<&'static ViewTarget, With<ExtractedView>>,
-    vertex_buffer: Option<Buffer>,
-    index_buffer: Option<Buffer>,
-    diffuse_bind_group: Option<BindGroup>,
-}
-
-impl BackgroundNode {
-    pub fn new(world: &mut World) -> Self {
-        Self {
-            query: QueryState::new(world),
-
-            vertex_buffer: None,
-            index_buffer: None,
-            diffuse_bind_group: None,
-        }
-    }
-}
-
-impl Node for BackgroundNode {
-    fn input(&self) -> Vec<SlotInfo> {
-        vec![]
-    }
-
-    fn update(&mut self, world: &mut World) {
-        self.query.update_archetypes(world);
-        if let Some(img) = world.get_resource::<BackgroundImage>() {
-            let device = world.get_resource::<RenderDevice>().unwrap();
-            let queue = world.get_resource::<RenderQueue>().unwrap();
-
-            if self.index_buffer.is_none() {
-                let index_buffer = device.create_buffer_with_data(&BufferInitDescriptor {
-                    label: Some("Index Buffer"),
-                    contents: bytemuck::cast_slice(INDICES),
-                    usage: BufferUsages::INDEX,
-                });
-                self.index_buffer = Some(index_buffer)
-            }
-            if self.vertex_buffer.is_none() {
-                let vertex_buffer = device.create_buffer_with_data(&BufferInitDescriptor {
-                    label: Some("Vertex Buffer"),
-                    contents: bytemuck::cast_slice(VERTICES),
-                    usage: BufferUsages::VERTEX,
-                });
-                self.vertex_buffer = Some(vertex_buffer)
-            }
-
-            let size = Extent3d {
-                width: img.width(),
-                height: img.height(),
-                depth_or_array_layers: 1,
-            };
-            let texture = device.create_texture(&TextureDescriptor {
-                label: Some("webcam_img"),
-                size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: TextureDimension::D2,
-                format: TextureFormat::Rgba8UnormSrgb,
-                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
-                view_formats: &[],
-            });
-            let format_size = img.texture_descriptor.format.pixel_size();
-            queue.write_texture(
-                texture.as_image_copy(),
-                img.data.as_ref().expect("Image has no data"),
-                TexelCopyBufferLayout {
-                    offset: 0,
-                    bytes_per_row: Some(img.width() * format_size as u32),
-                    rows_per_image: None,
-                },
-                img.texture_descriptor.size,
-            );
-
-            let view = texture.create_view(&TextureViewDescriptor::default());
-            let sampler = device.create_sampler(&SamplerDescriptor {
-                address_mode_u: AddressMode::ClampToEdge,
-                address_mode_v: AddressMode::ClampToEdge,
-                address_mode_w: AddressMode::ClampToEdge,
-                mag_filter: FilterMode::Linear,
-                min_filter: FilterMode::Nearest,
-                mipmap_filter: FilterMode::Nearest,
-                ..Default::default()
-            });
-
-            let texture_bind_group_layout = device.create_bind_group_layout(
-                "texture_bind_group_layout",
-                &[
-                    BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: ShaderStages::FRAGMENT,
-                        ty: BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: TextureViewDimension::D2,
-                            sample_type: TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: ShaderStages::FRAGMENT,
-                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-            );
-
-            let diffuse_bind_group = device.create_bind_group(
-                Some("diffuse_bind_group"),
-                &texture_bind_group_layout,
-                &BindGroupEntries::sequential((&view, &sampler)),
-            );
-
-            self.diffuse_bind_group = Some(diffuse_bind_group);
-        }
-    }
+impl ViewNode for BackgroundOverlayNode {
+    type ViewQuery = BackgroundViewQuery;
 
     fn run(
         &self,
         _graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
+        view: QueryItem<Self::ViewQuery>,
         world: &World,
     ) -> Result<(), NodeRunError> {
-        for target in self.query.iter_manual(world) {
-            let pipeline = world.get_resource::<BackgroundPipeline>().unwrap();
-            let pass_descriptor = RenderPassDescriptor {
-                label: Some("background_pass"),
-                color_attachments: &[Some(target.get_color_attachment())],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            };
-
-            if let (Some(vertex_buffer), Some(index_buffer)) =
-                (&self.vertex_buffer, &self.index_buffer)
-            {
-                let mut render_pass = render_context
-                    .command_encoder()
-                    .begin_render_pass(&pass_descriptor);
-
-                render_pass.set_pipeline(&pipeline.render_pipeline);
-
-                render_pass.set_bind_group(0, self.diffuse_bind_group.as_ref().unwrap(), &[]);
-                render_pass.set_vertex_buffer(0, *vertex_buffer.slice(..));
-                render_pass.set_index_buffer(*index_buffer.slice(..), IndexFormat::Uint16);
-
-                render_pass.draw_indexed(0..(INDICES.len() as u32), 0, 0..1);
-            }
-        }
-
+        draw_background(BackgroundOrder::Overlay, render_context, view, world);
         Ok(())
     }
 }
 
-pub fn handle_background_image(
-    mut image: ResMut<BackgroundImage>,
-    mut cam_query: Query<&mut GstCamera, With<BackgroundImageMarker>>,
+fn draw_background(
+    order: BackgroundOrder,
+    render_context: &mut RenderContext,
+    (target, depth, extracted, background_pipeline, background): QueryItem<BackgroundViewQuery>,
+    world: &World,
 ) {
-    if let Ok(mut cam) = cam_query.single_mut() {
-        if let Ok(frame) = cam.frame() {
-            let size = Extent3d {
-                width: frame.width(),
-                height: frame.height(),
-                depth_or_array_layers: 1,
-            };
-            let dimensions = TextureDimension::D2;
-            let format = TextureFormat::Rgba8Unorm;
-            let asset_usage = RenderAssetUsages::default();
-            image.0 = Image::new(size, dimensions, frame.to_vec(), format, asset_usage);
-        }
+    if extracted.composite.order != order {
+        return;
+    }
+    let uniforms = world.resource::<BackgroundUniforms>();
+    let Some(uniform_bind_group) = &uniforms.bind_group else {
+        return;
+    };
+    let pipeline = world.resource::<BackgroundPipeline>();
+    let Some(render_pipeline) = world
+        .resource::<PipelineCache>()
+        .get_render_pipeline(background_pipeline.0)
+    else {
+        // still compiling
+        return;
+    };
+
+    let depth_stencil_attachment = match (order, depth) {
+        (BackgroundOrder::Behind, Some(depth)) => Some(depth.get_attachment(StoreOp::Store)),
+        _ => None,
+    };
+    let pass_descriptor = RenderPassDescriptor {
+        label: Some("background_pass"),
+        color_attachments: &[Some(target.get_color_attachment())],
+        depth_stencil_attachment,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    };
+    let mut render_pass = render_context
+        .command_encoder()
+        .begin_render_pass(&pass_descriptor);
+
+    render_pass.set_pipeline(render_pipeline);
+    render_pass.set_bind_group(0, &background.bind_group, &[]);
+    render_pass.set_bind_group(1, uniform_bind_group, &[background.uniform_offset]);
+    render_pass.set_vertex_buffer(0, *pipeline.vertex_buffer.slice(..));
+    render_pass.set_index_buffer(*pipeline.index_buffer.slice(..), IndexFormat::Uint16);
+    render_pass.draw_indexed(0..(INDICES.len() as u32), 0, 0..1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn background_uniform_has_a_valid_layout() {
+        // encase panics on offsets the uniform address space does not allow
+        let mut buffer = DynamicUniformBuffer::<BackgroundUniform>::default();
+        buffer.push(&BackgroundFit::default().uniform(Vec2::ONE, Vec2::ONE));
+        buffer.push(&BackgroundFit::Cover.uniform(Vec2::new(4.0, 3.0), Vec2::ONE));
     }
 }
@@ -0,0 +1,104 @@
+#define_import_path bevy_gstreamer::background
+
+#import bevy_gstreamer::chroma_key::{ChromaKey, apply_chroma_key}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var s_diffuse: sampler;
+@group(0) @binding(2)
+var t_lut: texture_3d<f32>;
+@group(0) @binding(3)
+var s_lut: sampler;
+
+struct ColorGrading {
+    brightness: f32,
+    contrast: f32,
+    saturation: f32,
+    hue: f32,
+    gamma: f32,
+    enabled: u32,
+    lut: u32,
+};
+
+struct BackgroundUniform {
+    uv_offset: vec2<f32>,
+    uv_scale: vec2<f32>,
+    clear_color: vec4<f32>,
+    opacity: f32,
+    // seconds since startup, wrapped
+    time: f32,
+    chroma_key: ChromaKey,
+    grading: ColorGrading,
+    // free parameters of a custom shader
+    params: array<vec4<f32>, 4>,
+};
+
+@group(1) @binding(0)
+var<uniform> background: BackgroundUniform;
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3<f32>(2.4)), c / 12.92, c <= vec3<f32>(0.04045));
+}
+
+// grade a linear color in sRGB encoded values, like the feed was encoded
+fn color_grade(color: vec3<f32>, grading: ColorGrading) -> vec3<f32> {
+    if grading.enabled == 0u {
+        return color;
+    }
+
+    var c = linear_to_srgb(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)));
+    c = (c - 0.5) * grading.contrast + 0.5 + grading.brightness;
+    let luma = dot(c, vec3<f32>(0.2126, 0.7152, 0.0722));
+    c = mix(vec3<f32>(luma), c, grading.saturation);
+
+    // rotate around the grey axis
+    let k = vec3<f32>(0.57735);
+    let cos_hue = cos(grading.hue);
+    c = c * cos_hue + cross(k, c) * sin(grading.hue) + k * dot(k, c) * (1.0 - cos_hue);
+
+    c = pow(clamp(c, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(1.0 / grading.gamma));
+
+    if grading.lut != 0u {
+        // sample texel centers, the table covers [0, 1] from the first to the last texel
+        let size = vec3<f32>(textureDimensions(t_lut));
+        c = textureSampleLevel(t_lut, s_lut, (c * (size - 1.0) + 0.5) / size, 0.0).rgb;
+    }
+    return srgb_to_linear(c);
+}
+
+// frame texture coordinates of a view position, outside [0, 1] when letterboxed
+fn frame_uv(tex_coords: vec2<f32>) -> vec2<f32> {
+    return tex_coords * background.uv_scale + background.uv_offset;
+}
+
+// the graded and keyed frame, or the letterbox color outside of it
+fn sample_frame(uv: vec2<f32>) -> vec4<f32> {
+    // sample before branching, derivatives need uniform control flow
+    let color = textureSample(t_diffuse, s_diffuse, uv);
+    let inside = all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0));
+    let graded = vec4<f32>(color_grade(color.rgb, background.grading), color.a);
+    let keyed = apply_chroma_key(graded, background.chroma_key);
+    return select(background.clear_color, keyed, inside);
+}
+
+// apply the opacity and prepare the color for the blend mode of the view
+fn composite(color: vec4<f32>) -> vec4<f32> {
+    var out = color;
+    out.a *= background.opacity;
+#ifdef BLEND_MULTIPLY
+    // multiplier of the view color, fading to white with the alpha
+    return vec4<f32>(mix(vec3<f32>(1.0), out.rgb, out.a), 1.0);
+#else
+    return vec4<f32>(out.rgb * out.a, out.a);
+#endif
+}
@@ -0,0 +1,71 @@
+use bevy::asset::weak_handle;
+use bevy::prelude::*;
+use bevy::render::render_resource::ShaderType;
+
+pub(crate) const CHROMA_KEY_SHADER_HANDLE: Handle<Shader> =
+    weak_handle!("0c6e4a52-8d1f-4b7a-a3e9-61f2d5b08c47");
+
+/// Make a key color of the camera frame transparent, e.g. a green screen
+///
+/// Add it to a bevy camera with a [`CameraBackground`](crate::camera::CameraBackground) to key
+/// the background, combined with [`BackgroundBlend::Alpha`](crate::camera::BackgroundBlend) to
+/// see the view through, or set it on a [`VideoMaterial`](crate::camera::VideoMaterial).
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+#[reflect(Component, Default)]
+pub struct ChromaKey {
+    pub color: Color,
+    /// chroma distance to the key color that is fully transparent
+    pub similarity: f32,
+    /// width of the transition from transparent to opaque
+    pub smoothness: f32,
+    /// width of the range where the key color spilling on the subject is desaturated
+    pub spill: f32,
+}
+
+impl Default for ChromaKey {
+    fn default() -> Self {
+        Self {
+            color: Color::srgb(0.0, 1.0, 0.0),
+            similarity: 0.25,
+            smoothness: 0.08,
+            spill: 0.1,
+        }
+    }
+}
+
+impl ChromaKey {
+    pub fn new(color: Color) -> Self {
+        Self {
+            color,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn uniform(key: Option<&ChromaKey>) -> ChromaKeyUniform {
+        match key {
+            Some(key) => ChromaKeyUniform {
+                color: key.color.to_linear().to_vec4(),
+                similarity: key.similarity,
+                smoothness: key.smoothness,
+                spill: key.spill,
+                enabled: 1,
+            },
+            None => ChromaKeyUniform {
+                color: Vec4::ZERO,
+                similarity: 0.0,
+                smoothness: 0.0,
+                spill: 0.0,
+                enabled: 0,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ShaderType)]
+pub struct ChromaKeyUniform {
+    pub color: Vec4,
+    pub similarity: f32,
+    pub smoothness: f32,
+    pub spill: f32,
+    pub enabled: u32,
+}
@@ -0,0 +1,30 @@
+#define_import_path bevy_gstreamer::chroma_key
+
+struct ChromaKey {
+    color: vec4<f32>,
+    similarity: f32,
+    smoothness: f32,
+    spill: f32,
+    enabled: u32,
+};
+
+// chroma of a linear rgb color, BT.709
+fn chroma(rgb: vec3<f32>) -> vec2<f32> {
+    return vec2<f32>(
+        dot(rgb, vec3<f32>(-0.1146, -0.3854, 0.5)),
+        dot(rgb, vec3<f32>(0.5, -0.4542, -0.0458)),
+    );
+}
+
+// make colors close to the key transparent and desaturate the key color spilling on the rest
+fn apply_chroma_key(color: vec4<f32>, key: ChromaKey) -> vec4<f32> {
+    if key.enabled == 0u {
+        return color;
+    }
+
+    let mask = distance(chroma(color.rgb), chroma(key.color.rgb)) - key.similarity;
+    let alpha = pow(clamp(mask / max(key.smoothness, 0.0001), 0.0, 1.0), 1.5);
+    let spill = pow(clamp(mask / max(key.spill, 0.0001), 0.0, 1.0), 1.5);
+    let luma = dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+    return vec4<f32>(mix(vec3<f32>(luma), color.rgb, spill), color.a * alpha);
+}
@@ -0,0 +1,369 @@
+use gstreamer::prelude::*;
+use gstreamer::{Element, Structure};
+use gstreamer_video::ColorBalance;
+use gstreamer_video::prelude::ColorBalanceExt;
+
+use crate::error::BevyGstError;
+use crate::types::{CameraControl, ControlKind, KnownCameraControl};
+
+/// list the controls exposed by a capture source
+///
+/// `v4l2src` is queried directly on its device with the `v4l2` feature, other sources are read through
+/// `GstColorBalance`, which does not report defaults, so the current value is used instead.
+pub(crate) fn list_controls(source: &Element) -> Result<Vec<CameraControl>, BevyGstError> {
+    #[cfg(all(target_os = "linux", feature = "v4l2"))]
+    if source.has_property("device-fd", None) {
+        return v4l2::list_controls(source);
+    }
+
+    Ok(color_balance_controls(source))
+}
+
+/// set a control on a capture source
+///
+/// For `v4l2src` the value is recorded in `extra_controls`, which is applied to the device
+/// right away and again whenever the pipeline is rebuilt.
+pub(crate) fn set_control(
+    source: &Element,
+    extra_controls: &mut Structure,
+    control: &CameraControl,
+    value: i32,
+) -> Result<(), BevyGstError> {
+    check_value(control, value)?;
+
+    if source.has_property("extra-controls", None) {
+        extra_controls.set(normalise_control_name(&control.name), value);
+        apply_extra_controls(source, extra_controls);
+        return Ok(());
+    }
+
+    let balance = match source.dynamic_cast_ref::<ColorBalance>() {
+        Some(balance) => balance,
+        None => {
+            return Err(BevyGstError::SetPropertyError {
+                property: control.name.clone(),
+                value: value.to_string(),
+                error: "Source has no controls".to_string(),
+            });
+        }
+    };
+    match balance
+        .list_channels()
+        .iter()
+        .find(|channel| channel.label() == control.name.as_str())
+    {
+        Some(channel) => {
+            balance.set_value(channel, value);
+            Ok(())
+        }
+        None => Err(BevyGstError::SetPropertyError {
+            property: control.name.clone(),
+            value: value.to_string(),
+            error: "No such color balance channel".to_string(),
+        }),
+    }
+}
+
+/// reject values the driver would refuse, out of range, between steps or no menu entry
+fn check_value(control: &CameraControl, value: i32) -> Result<(), BevyGstError> {
+    let error = if value < control.min || value > control.max {
+        format!("Out of range [{}, {}]", control.min, control.max)
+    } else if control.kind == ControlKind::Menu {
+        if control.menu.is_empty() || control.menu.contains(&value) {
+            return Ok(());
+        }
+        format!("Not one of the menu entries {:?}", control.menu)
+    } else if control.step > 1
+        && (i64::from(value) - i64::from(control.min)) % i64::from(control.step) != 0
+    {
+        format!("Not {} plus a multiple of {}", control.min, control.step)
+    } else {
+        return Ok(());
+    };
+    Err(BevyGstError::SetPropertyError {
+        property: control.name.clone(),
+        value: value.to_string(),
+        error,
+    })
+}
+
+/// apply previously set controls to a (re)created source
+pub(crate) fn apply_extra_controls(source: &Element, extra_controls: &Structure) {
+    if extra_controls.n_fields() > 0 && source.has_property("extra-controls", None) {
+        source.set_property("extra-controls", extra_controls);
+    }
+}
+
+/// the value switching the auto mode `control` on or off
+pub(crate) fn auto_value(control: &CameraControl, enabled: bool) -> i32 {
+    match (control.control, enabled) {
+        // V4L2_EXPOSURE_MANUAL; UVC devices usually only offer aperture priority for auto
+        (KnownCameraControl::ExposureAuto, false) => 1,
+        (KnownCameraControl::ExposureAuto, true) if control.max >= 3 => 3,
+        (KnownCameraControl::ExposureAuto, true) => 0,
+        (_, enabled) => i32::from(enabled),
+    }
+}
+
+/// normalise a control name the way `v4l2src` names `extra-controls` fields
+fn normalise_control_name(name: &str) -> String {
+    let mut normalised = String::with_capacity(name.len());
+    let mut previous_alphanumeric = true;
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            if !previous_alphanumeric && !normalised.is_empty() {
+                normalised.push('_');
+            }
+            normalised.push(c.to_ascii_lowercase());
+        }
+        previous_alphanumeric = c.is_ascii_alphanumeric();
+    }
+    normalised
+}
+
+fn color_balance_controls(source: &Element) -> Vec<CameraControl> {
+    let balance = match source.dynamic_cast_ref::<ColorBalance>() {
+        Some(balance) => balance,
+        None => return vec![],
+    };
+
+    balance
+        .list_channels()
+        .iter()
+        .enumerate()
+        .map(|(index, channel)| {
+            let label = channel.label();
+            let value = balance.value(channel);
+            CameraControl {
+                control: control_from_label(&label)
+                    .unwrap_or(KnownCameraControl::Other(index as u32)),
+                name: label.to_string(),
+                kind: ControlKind::Integer,
+                min: channel.min_value(),
+                max: channel.max_value(),
+                step: 1,
+                default: value,
+                value,
+                active: true,
+                menu: vec![],
+            }
+        })
+        .collect()
+}
+
+fn control_from_label(label: &str) -> Option<KnownCameraControl> {
+    match label.to_ascii_lowercase().as_str() {
+        "brightness" => Some(KnownCameraControl::Brightness),
+        "contrast" => Some(KnownCameraControl::Contrast),
+        "saturation" => Some(KnownCameraControl::Saturation),
+        "hue" => Some(KnownCameraControl::Hue),
+        _ => None,
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "v4l2"))]
+mod v4l2 {
+    use std::os::fd::RawFd;
+
+    use gstreamer::Element;
+    use gstreamer::prelude::*;
+
+    use crate::error::BevyGstError;
+    use crate::types::{CameraControl, ControlKind, KnownCameraControl};
+
+    const VIDIOC_G_CTRL: u32 = 0xc008_561b;
+    const VIDIOC_QUERYCTRL: u32 = 0xc044_5624;
+    const VIDIOC_QUERYMENU: u32 = 0xc02c_5625;
+
+    const V4L2_CTRL_FLAG_DISABLED: u32 = 0x0001;
+    const V4L2_CTRL_FLAG_INACTIVE: u32 = 0x0010;
+    const V4L2_CTRL_FLAG_NEXT_CTRL: u32 = 0x8000_0000;
+
+    const V4L2_CTRL_TYPE_INTEGER: u32 = 1;
+    const V4L2_CTRL_TYPE_BOOLEAN: u32 = 2;
+    const V4L2_CTRL_TYPE_MENU: u32 = 3;
+    const V4L2_CTRL_TYPE_INTEGER_MENU: u32 = 9;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct QueryCtrl {
+        id: u32,
+        kind: u32,
+        name: [u8; 32],
+        minimum: i32,
+        maximum: i32,
+        step: i32,
+        default_value: i32,
+        flags: u32,
+        reserved: [u32; 2],
+    }
+
+    #[repr(C)]
+    struct QueryMenu {
+        id: u32,
+        index: u32,
+        /// the name, or the value of integer menus
+        name: [u8; 32],
+        reserved: u32,
+    }
+
+    #[repr(C)]
+    struct Control {
+        id: u32,
+        value: i32,
+    }
+
+    pub(super) fn list_controls(source: &Element) -> Result<Vec<CameraControl>, BevyGstError> {
+        let fd = source.property::<i32>("device-fd");
+        if fd < 0 {
+            return Err(BevyGstError::GetPropertyError {
+                property: "Controls".to_string(),
+                error: "Device is not open".to_string(),
+            });
+        }
+
+        let mut controls = vec![];
+        let mut query = QueryCtrl {
+            id: V4L2_CTRL_FLAG_NEXT_CTRL,
+            ..Default::default()
+        };
+        // SAFETY: `query` is a properly sized `struct v4l2_queryctrl` for the whole loop
+        while unsafe { libc::ioctl(fd, VIDIOC_QUERYCTRL as libc::Ioctl, &mut query) } == 0 {
+            let kind = match query.kind {
+                V4L2_CTRL_TYPE_INTEGER => Some(ControlKind::Integer),
+                V4L2_CTRL_TYPE_BOOLEAN => Some(ControlKind::Boolean),
+                V4L2_CTRL_TYPE_MENU | V4L2_CTRL_TYPE_INTEGER_MENU => Some(ControlKind::Menu),
+                // buttons, classes, strings and 64 bit controls are not supported
+                _ => None,
+            };
+
+            if let Some(kind) = kind
+                && query.flags & V4L2_CTRL_FLAG_DISABLED == 0
+            {
+                controls.push(CameraControl {
+                    control: known_control(query.id),
+                    name: control_name(&query.name),
+                    kind,
+                    min: query.minimum,
+                    max: query.maximum,
+                    step: query.step,
+                    default: query.default_value,
+                    value: control_value(fd, query.id)?,
+                    active: query.flags & V4L2_CTRL_FLAG_INACTIVE == 0,
+                    menu: match kind {
+                        ControlKind::Menu => menu_entries(fd, &query),
+                        _ => vec![],
+                    },
+                });
+            }
+
+            query = QueryCtrl {
+                id: query.id | V4L2_CTRL_FLAG_NEXT_CTRL,
+                ..Default::default()
+            };
+        }
+
+        Ok(controls)
+    }
+
+    /// the menu indices the driver accepts, menus may skip some between minimum and maximum
+    fn menu_entries(fd: RawFd, query: &QueryCtrl) -> Vec<i32> {
+        (query.minimum..=query.maximum)
+            .filter(|index| {
+                let mut menu = QueryMenu {
+                    id: query.id,
+                    index: *index as u32,
+                    name: [0; 32],
+                    reserved: 0,
+                };
+                // SAFETY: `menu` is a properly sized `struct v4l2_querymenu`
+                unsafe { libc::ioctl(fd, VIDIOC_QUERYMENU as libc::Ioctl, &mut menu) == 0 }
+            })
+            .collect()
+    }
+
+    fn control_value(fd: RawFd, id: u32) -> Result<i32, BevyGstError> {
+        let mut control = Control { id, value: 0 };
+        // SAFETY: `control` is a properly sized `struct v4l2_control`
+        if unsafe { libc::ioctl(fd, VIDIOC_G_CTRL as libc::Ioctl, &mut control) } != 0 {
+            return Err(BevyGstError::GetPropertyError {
+                property: format!("Control {id:#x}"),
+                error: std::io::Error::last_os_error().to_string(),
+            });
+        }
+        Ok(control.value)
+    }
+
+    fn control_name(name: &[u8; 32]) -> String {
+        let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        String::from_utf8_lossy(&name[..len]).into_owned()
+    }
+
+    fn known_control(id: u32) -> KnownCameraControl {
+        match id {
+            0x0098_0900 => KnownCameraControl::Brightness,
+            0x0098_0901 => KnownCameraControl::Contrast,
+            0x0098_0902 => KnownCameraControl::Saturation,
+            0x0098_0903 => KnownCameraControl::Hue,
+            0x0098_090c => KnownCameraControl::WhiteBalanceAuto,
+            0x0098_0910 => KnownCameraControl::Gamma,
+            0x0098_0911 | 0x009a_0902 => KnownCameraControl::Exposure,
+            0x0098_0912 => KnownCameraControl::GainAuto,
+            0x0098_0913 => KnownCameraControl::Gain,
+            0x0098_0918 => KnownCameraControl::PowerLineFrequency,
+            0x0098_091a => KnownCameraControl::WhiteBalance,
+            0x0098_091b => KnownCameraControl::Sharpness,
+            0x0098_091c => KnownCameraControl::BacklightCompensation,
+            0x009a_0901 => KnownCameraControl::ExposureAuto,
+            0x009a_090a => KnownCameraControl::Focus,
+            0x009a_090c => KnownCameraControl::FocusAuto,
+            0x009a_090d => KnownCameraControl::Zoom,
+            other => KnownCameraControl::Other(other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn control(kind: ControlKind, min: i32, max: i32, step: i32, menu: Vec<i32>) -> CameraControl {
+        CameraControl {
+            control: KnownCameraControl::Other(0),
+            name: "test".to_string(),
+            kind,
+            min,
+            max,
+            step,
+            default: min,
+            value: min,
+            active: true,
+            menu,
+        }
+    }
+
+    #[test]
+    fn values_must_be_in_range() {
+        let control = control(ControlKind::Integer, -10, 10, 1, vec![]);
+        assert!(check_value(&control, -10).is_ok());
+        assert!(check_value(&control, 10).is_ok());
+        assert!(check_value(&control, 11).is_err());
+        assert!(check_value(&control, -11).is_err());
+    }
+
+    #[test]
+    fn values_must_be_on_a_step() {
+        let control = control(ControlKind::Integer, 3, 23, 5, vec![]);
+        assert!(check_value(&control, 3).is_ok());
+        assert!(check_value(&control, 18).is_ok());
+        assert!(check_value(&control, 10).is_err());
+    }
+
+    #[test]
+    fn menu_values_must_be_entries() {
+        let control = control(ControlKind::Menu, 0, 3, 1, vec![1, 3]);
+        assert!(check_value(&control, 1).is_ok());
+        assert!(check_value(&control, 2).is_err());
+        assert!(check_value(&control, 4).is_err());
+    }
+}
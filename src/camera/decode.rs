@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+use gstreamer::ElementFactory;
+
+use crate::types::FrameFormat;
+
+/// Where MJPEG frames are decoded, to compare the routes per machine
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+pub struct MjpegDecoding {
+    pub decoder: MjpegDecoder,
+    /// threads of `videoconvert` and `avdec_mjpeg`, 0 for one per core
+    pub threads: u32,
+}
+
+impl MjpegDecoding {
+    pub fn new(decoder: MjpegDecoder) -> Self {
+        Self {
+            decoder,
+            ..Default::default()
+        }
+    }
+
+    /// the elements decoding MJPEG into RGB in the pipeline, `None` to decode in the appsink
+    ///
+    /// A missing decoder element falls back to the appsink with the `cpu-mjpeg` feature and to
+    /// `jpegdec` without it.
+    pub(crate) fn elements(&self) -> Option<String> {
+        let decoder = match self.decoder {
+            MjpegDecoder::Cpu if cfg!(feature = "cpu-mjpeg") => return None,
+            // without jpeg-decoder the pipeline always decodes
+            MjpegDecoder::Cpu | MjpegDecoder::Auto => [
+                MjpegDecoder::Vaapi,
+                MjpegDecoder::Avdec,
+                MjpegDecoder::Jpegdec,
+            ]
+            .into_iter()
+            .find(MjpegDecoder::is_available)
+            .unwrap_or(MjpegDecoder::Jpegdec),
+            decoder if decoder.is_available() => decoder,
+            decoder => {
+                warn!(
+                    "MJPEG decoder {} is not installed, falling back",
+                    decoder.element().unwrap_or_default()
+                );
+                return Self {
+                    decoder: MjpegDecoder::Cpu,
+                    ..*self
+                }
+                .elements();
+            }
+        };
+
+        let decoder = match decoder {
+            MjpegDecoder::Vaapi => "jpegparse ! vaapijpegdec".to_string(),
+            MjpegDecoder::Avdec => format!("avdec_mjpeg max-threads={}", self.threads),
+            _ => "jpegdec".to_string(),
+        };
+        // RGB like the frames decoded in the appsink
+        Some(format!(
+            "{decoder} ! videoconvert n-threads={} ! video/x-raw,format=RGB",
+            self.threads
+        ))
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+pub enum MjpegDecoder {
+    /// `jpeg-decoder` in the appsink callback, single threaded
+    #[default]
+    Cpu,
+    /// the first installed of `vaapijpegdec`, `avdec_mjpeg` and `jpegdec`
+    Auto,
+    /// `jpegdec` from gst-plugins-good, libjpeg based
+    Jpegdec,
+    /// `vaapijpegdec`, hardware decoding on VA-API GPUs
+    Vaapi,
+    /// `avdec_mjpeg` from gst-libav, multi threaded
+    Avdec,
+}
+
+impl MjpegDecoder {
+    /// the gstreamer element of the decoder
+    pub fn element(&self) -> Option<&'static str> {
+        match self {
+            MjpegDecoder::Cpu | MjpegDecoder::Auto => None,
+            MjpegDecoder::Jpegdec => Some("jpegdec"),
+            MjpegDecoder::Vaapi => Some("vaapijpegdec"),
+            MjpegDecoder::Avdec => Some("avdec_mjpeg"),
+        }
+    }
+
+    /// whether the decoder element is installed
+    pub fn is_available(&self) -> bool {
+        self.element()
+            .is_some_and(|element| ElementFactory::find(element).is_some())
+    }
+}
+
+/// the elements decoding a compressed stream into RGB, `None` for raw formats and MJPEG
+///
+/// Software decoders are preferred for predictable output, `decodebin` picks one when none of
+/// them is installed.
+pub(crate) fn stream_decoder(format: FrameFormat) -> Option<String> {
+    let (parser, decoders): (Option<&str>, &[&str]) = match format {
+        FrameFormat::H264 => (Some("h264parse"), &["avdec_h264", "openh264dec"]),
+        FrameFormat::H265 => (Some("h265parse"), &["avdec_h265"]),
+        FrameFormat::VP8 => (None, &["vp8dec", "avdec_vp8"]),
+        FrameFormat::VP9 => (Some("vp9parse"), &["vp9dec", "avdec_vp9"]),
+        _ => return None,
+    };
+    let installed = |element: &&str| ElementFactory::find(element).is_some();
+
+    let decoder = match decoders.iter().copied().find(installed) {
+        Some(decoder) => match parser.filter(installed) {
+            Some(parser) => format!("{parser} ! {decoder}"),
+            None => decoder.to_string(),
+        },
+        None => {
+            warn!("no {format} decoder of {decoders:?} is installed, trying decodebin");
+            "decodebin".to_string()
+        }
+    };
+    Some(format!("{decoder} ! videoconvert ! video/x-raw,format=RGB"))
+}
@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use gstreamer::{BufferFlags, Caps, ClockTime, ElementFactory, FlowError, FlowSuccess};
+use gstreamer_app::{AppSink, AppSinkCallbacks};
+
+use crate::types::FrameFormat;
+
+/// the appsink receiving samples before decoding
+pub(crate) const ENCODED_SINK: &str = "appsink name=encoded_sink async=false sync=false";
+
+/// samples kept until they are taken, older ones are dropped
+const QUEUE_SIZE: usize = 256;
+
+pub(crate) type EncodedQueue = VecDeque<EncodedSample>;
+
+/// Whether a camera hands out the samples it receives before they are decoded
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+pub enum EncodedPassthrough {
+    #[default]
+    Off,
+    /// encoded samples and decoded frames
+    Alongside,
+    /// encoded samples only, nothing is decoded and the camera produces no frames
+    Only,
+}
+
+/// One sample as the camera sent it, e.g. a JPEG image or an H.264 access unit
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncodedSample {
+    pub data: Vec<u8>,
+    /// running time of the pipeline the sample is presented at, like decoded frames
+    pub pts: Option<Duration>,
+    /// running time the sample is decoded at, earlier than `pts` for reordered frames
+    pub dts: Option<Duration>,
+    pub duration: Option<Duration>,
+    /// decodable without the samples before it
+    pub keyframe: bool,
+    /// the caps of the stream, set on the first sample and whenever they change
+    pub caps: Option<String>,
+}
+
+/// parse H.264 and H.265 into access units repeating their parameter sets at every keyframe
+///
+/// Forwarded and recorded streams can then be joined at any keyframe.
+pub(crate) fn parser(format: FrameFormat) -> String {
+    let (parser, caps) = match format {
+        FrameFormat::H264 => ("h264parse", "video/x-h264"),
+        FrameFormat::H265 => ("h265parse", "video/x-h265"),
+        _ => return String::new(),
+    };
+    if ElementFactory::find(parser).is_none() {
+        warn!("{parser} is not installed, encoded samples may lack keyframe flags");
+        return String::new();
+    }
+    format!(" ! {parser} config-interval=-1 ! {caps},stream-format=byte-stream,alignment=au")
+}
+
+/// queue the samples of the `encoded_sink`
+pub(crate) fn attach_sink(appsink: &AppSink, queue: Arc<Mutex<EncodedQueue>>) {
+    let mut last_caps: Option<Caps> = None;
+    let mut warned = false;
+
+    appsink.set_callbacks(
+        AppSinkCallbacks::builder()
+            .new_sample(move |appsink| {
+                let sample = appsink.pull_sample().map_err(|_| FlowError::Eos)?;
+                let Some(buffer) = sample.buffer() else {
+                    return Err(FlowError::Error);
+                };
+                let segment = sample
+                    .segment()
+                    .and_then(|segment| segment.downcast_ref::<ClockTime>());
+                let running_time = |time: Option<ClockTime>| {
+                    segment
+                        .and_then(|segment| segment.to_running_time(time))
+                        .map(Duration::from)
+                };
+
+                let caps = match sample.caps() {
+                    Some(caps) if last_caps.as_deref() != Some(caps) => {
+                        last_caps = Some(caps.to_owned());
+                        Some(caps.to_string())
+                    }
+                    _ => None,
+                };
+                let data = match buffer.map_readable() {
+                    Ok(map) => map.to_vec(),
+                    Err(_) => return Err(FlowError::Error),
+                };
+
+                let encoded = EncodedSample {
+                    data,
+                    pts: running_time(buffer.pts()),
+                    dts: running_time(buffer.dts()),
+                    duration: buffer.duration().map(Duration::from),
+                    keyframe: !buffer.flags().contains(BufferFlags::DELTA_UNIT),
+                    caps,
+                };
+
+                if let Ok(mut queue) = queue.lock() {
+                    if queue.len() >= QUEUE_SIZE {
+                        // keep the caps of the dropped sample for the next one
+                        let dropped = queue.pop_front().and_then(|sample| sample.caps);
+                        if let Some(next) = queue.front_mut() {
+                            next.caps = next.caps.take().or(dropped);
+                        }
+                        if !warned {
+                            warn!("encoded samples are not taken fast enough, dropping the oldest");
+                            warned = true;
+                        }
+                    }
+                    queue.push_back(encoded);
+                }
+
+                Ok(FlowSuccess::Ok)
+            })
+            .build(),
+    );
+}
@@ -0,0 +1,133 @@
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma, Rgb, RgbImage, Rgba, RgbaImage};
+
+/// Pixel layout of a [`FrameView`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    Rgb8,
+    Rgba8,
+    Gray8,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::Rgba8 => 4,
+            PixelFormat::Gray8 => 1,
+        }
+    }
+}
+
+/// A decoded frame borrowed from the camera, see [`GstCamera::with_frame`](crate::camera::GstCamera::with_frame)
+#[derive(Clone, Copy, Debug)]
+pub struct FrameView<'a> {
+    data: &'a [u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    format: PixelFormat,
+}
+
+impl<'a> FrameView<'a> {
+    pub(crate) fn new(
+        data: &'a [u8],
+        width: u32,
+        height: u32,
+        stride: usize,
+        format: PixelFormat,
+    ) -> Self {
+        Self {
+            data,
+            width,
+            height,
+            stride,
+            format,
+        }
+    }
+
+    pub(crate) fn from_rgb(image: &'a ImageBuffer<Rgb<u8>, Vec<u8>>) -> Self {
+        let stride = image.width() as usize * PixelFormat::Rgb8.bytes_per_pixel();
+        Self::new(
+            image.as_raw(),
+            image.width(),
+            image.height(),
+            stride,
+            PixelFormat::Rgb8,
+        )
+    }
+
+    /// the pixel data, `stride` bytes per row
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// bytes from the start of one row to the next
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// the pixels of row `y`, without padding
+    pub fn row(&self, y: u32) -> &'a [u8] {
+        let start = y as usize * self.stride;
+        &self.data[start..start + self.width as usize * self.format.bytes_per_pixel()]
+    }
+
+    /// copy into an image of the same pixel format
+    pub fn to_dynamic_image(self) -> DynamicImage {
+        match self.format {
+            PixelFormat::Rgb8 => DynamicImage::ImageRgb8(self.to_buffer::<Rgb<u8>>()),
+            PixelFormat::Rgba8 => DynamicImage::ImageRgba8(self.to_buffer::<Rgba<u8>>()),
+            PixelFormat::Gray8 => DynamicImage::ImageLuma8(self.to_buffer::<Luma<u8>>()),
+        }
+    }
+
+    pub fn to_rgb_image(self) -> RgbImage {
+        match self.format {
+            PixelFormat::Rgb8 => self.to_buffer(),
+            _ => self.to_dynamic_image().into_rgb8(),
+        }
+    }
+
+    pub fn to_rgba_image(self) -> RgbaImage {
+        match self.format {
+            PixelFormat::Rgba8 => self.to_buffer(),
+            _ => self.to_dynamic_image().into_rgba8(),
+        }
+    }
+
+    pub fn to_gray_image(self) -> GrayImage {
+        match self.format {
+            PixelFormat::Gray8 => self.to_buffer(),
+            _ => self.to_dynamic_image().into_luma8(),
+        }
+    }
+
+    /// copy the rows, dropping any padding
+    fn to_buffer<P: image::Pixel<Subpixel = u8>>(self) -> ImageBuffer<P, Vec<u8>> {
+        let mut data = Vec::with_capacity(
+            self.width as usize * self.height as usize * self.format.bytes_per_pixel(),
+        );
+        for y in 0..self.height {
+            data.extend_from_slice(self.row(y));
+        }
+        ImageBuffer::from_raw(self.width, self.height, data).expect("frame data matches its size")
+    }
+}
+
+impl From<FrameView<'_>> for DynamicImage {
+    fn from(frame: FrameView<'_>) -> Self {
+        frame.to_dynamic_image()
+    }
+}
@@ -0,0 +1,225 @@
+use std::path::Path;
+
+use bevy::asset::RenderAssetUsages;
+use bevy::image::ImageSampler;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, ShaderType, TextureDimension, TextureFormat};
+
+use crate::error::BevyGstError;
+
+/// Color adjustments of a camera feed, add it next to a [`GstCamera`](crate::camera::GstCamera)
+///
+/// Applied where the feed is drawn as a background, in sRGB encoded values, before the chroma key.
+#[derive(Component, Clone, Debug, PartialEq, Reflect)]
+#[reflect(Component, Default)]
+pub struct GstColorGrading {
+    /// added to each channel, 0 keeps the feed
+    pub brightness: f32,
+    /// scale around mid grey, 1 keeps the feed
+    pub contrast: f32,
+    /// 0 is greyscale, 1 keeps the feed
+    pub saturation: f32,
+    /// hue rotation in radians
+    pub hue: f32,
+    /// > 1 brightens mid tones, 1 keeps the feed
+    pub gamma: f32,
+    /// a 3D LUT applied last, see [`load_cube_lut`]
+    pub lut: Option<Handle<Image>>,
+}
+
+impl Default for GstColorGrading {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            hue: 0.0,
+            gamma: 1.0,
+            lut: None,
+        }
+    }
+}
+
+impl GstColorGrading {
+    pub(crate) fn uniform(grading: Option<&GstColorGrading>, has_lut: bool) -> ColorGradingUniform {
+        let enabled = grading.is_some();
+        let grading = grading.cloned().unwrap_or_default();
+        ColorGradingUniform {
+            brightness: grading.brightness,
+            contrast: grading.contrast,
+            saturation: grading.saturation,
+            hue: grading.hue,
+            gamma: grading.gamma.max(0.001),
+            enabled: u32::from(enabled),
+            lut: u32::from(enabled && has_lut),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ShaderType)]
+pub struct ColorGradingUniform {
+    pub brightness: f32,
+    pub contrast: f32,
+    pub saturation: f32,
+    pub hue: f32,
+    pub gamma: f32,
+    pub enabled: u32,
+    pub lut: u32,
+}
+
+/// load a 3D LUT in the Adobe/Resolve `.cube` format as an image for [`GstColorGrading::lut`]
+///
+/// The table is stored as 8 bit per channel, input values outside the domain are clamped.
+pub fn load_cube_lut(path: impl AsRef<Path>) -> Result<Image, BevyGstError> {
+    let path = path.as_ref();
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(why) => {
+            return Err(BevyGstError::LutError(
+                path.display().to_string(),
+                why.to_string(),
+            ));
+        }
+    };
+    parse_cube_lut(&text).map_err(|why| BevyGstError::LutError(path.display().to_string(), why))
+}
+
+fn parse_cube_lut(text: &str) -> Result<Image, String> {
+    let mut size = None;
+    let mut domain_min = Vec3::ZERO;
+    let mut domain_max = Vec3::ONE;
+    let mut data = vec![];
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut words = line.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue;
+        };
+        match keyword {
+            "TITLE" => {}
+            "LUT_1D_SIZE" => return Err("1D LUTs are not supported".to_string()),
+            "LUT_3D_SIZE" => {
+                size = match words.next().map(str::parse::<u32>) {
+                    Some(Ok(size)) if (2..=256).contains(&size) => Some(size),
+                    _ => return Err(format!("Invalid size: {line}")),
+                }
+            }
+            "DOMAIN_MIN" => domain_min = parse_vec3(words)?,
+            "DOMAIN_MAX" => domain_max = parse_vec3(words)?,
+            _ => {
+                let value = parse_vec3(line.split_whitespace())?;
+                let value = (value - domain_min) / (domain_max - domain_min).max(Vec3::splat(1e-6));
+                let value = (value.clamp(Vec3::ZERO, Vec3::ONE) * 255.0).round();
+                data.extend_from_slice(&[value.x as u8, value.y as u8, value.z as u8, 255]);
+            }
+        }
+    }
+
+    let size = size.ok_or_else(|| "Missing LUT_3D_SIZE".to_string())?;
+    if data.len() != (size * size * size * 4) as usize {
+        return Err(format!(
+            "Expected {} entries, found {}",
+            size * size * size,
+            data.len() / 4
+        ));
+    }
+
+    // red changes fastest, so red, green and blue map to x, y and z
+    let mut image = Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: size,
+        },
+        TextureDimension::D3,
+        data,
+        TextureFormat::Rgba8Unorm,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.sampler = ImageSampler::linear();
+    Ok(image)
+}
+
+fn parse_vec3<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<Vec3, String> {
+    let mut value = [0.0; 3];
+    for v in &mut value {
+        *v = match words.next().map(str::parse::<f32>) {
+            Some(Ok(v)) => v,
+            _ => return Err("Expected three numbers".to_string()),
+        };
+    }
+    Ok(Vec3::from_array(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IDENTITY: &str = "\
+# 2x2x2 identity
+TITLE \"identity\"
+LUT_3D_SIZE 2
+0 0 0
+1 0 0
+0 1 0
+1 1 0
+0 0 1
+1 0 1
+0 1 1
+1 1 1
+";
+
+    fn texels(image: &Image) -> Vec<[u8; 4]> {
+        image
+            .data
+            .as_ref()
+            .unwrap()
+            .chunks_exact(4)
+            .map(|texel| [texel[0], texel[1], texel[2], texel[3]])
+            .collect()
+    }
+
+    #[test]
+    fn identity_lut_is_red_fastest() {
+        let image = parse_cube_lut(IDENTITY).unwrap();
+        assert_eq!(image.texture_descriptor.size.width, 2);
+        assert_eq!(image.texture_descriptor.size.depth_or_array_layers, 2);
+        assert_eq!(image.texture_descriptor.dimension, TextureDimension::D3);
+
+        let texels = texels(&image);
+        assert_eq!(texels.len(), 8);
+        for (i, texel) in texels.iter().enumerate() {
+            let channel = |bit: usize| if i & bit != 0 { 255 } else { 0 };
+            assert_eq!(*texel, [channel(1), channel(2), channel(4), 255]);
+        }
+    }
+
+    #[test]
+    fn domain_is_rescaled() {
+        let text = IDENTITY.replace("1", "2").replace(
+            "LUT_3D_SIZE 2",
+            "LUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\nDOMAIN_MAX 2 2 2",
+        );
+        let texels = texels(&parse_cube_lut(&text).unwrap());
+        assert_eq!(texels[1], [255, 0, 0, 255]);
+        assert_eq!(texels[7], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn invalid_luts_are_rejected() {
+        assert!(parse_cube_lut("LUT_1D_SIZE 2\n0 0 0\n1 1 1").is_err());
+        assert!(parse_cube_lut(&IDENTITY.replace("LUT_3D_SIZE 2", "LUT_3D_SIZE x")).is_err());
+        assert!(parse_cube_lut(&IDENTITY.replace("LUT_3D_SIZE 2", "LUT_3D_SIZE 1")).is_err());
+        assert!(parse_cube_lut(&IDENTITY.replace("LUT_3D_SIZE 2", "")).is_err());
+        assert!(parse_cube_lut(&IDENTITY.replace("1 1 1\n", "")).is_err());
+        assert!(parse_cube_lut(&format!("{IDENTITY}0 0 0\n")).is_err());
+        assert!(
+            parse_cube_lut(&IDENTITY.replace("LUT_3D_SIZE 2", "LUT_3D_SIZE 2\nDOMAIN_MAX 1 1"))
+                .is_err()
+        );
+    }
+}
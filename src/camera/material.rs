@@ -0,0 +1,85 @@
+use bevy::asset::weak_handle;
+use bevy::pbr::{ExtendedMaterial, MaterialExtension};
+use bevy::platform::collections::HashSet;
+use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_resource::{AsBindGroup, AsBindGroupShaderType, ShaderRef};
+use bevy::render::texture::GpuImage;
+
+use crate::camera::chroma_key::{ChromaKey, ChromaKeyUniform};
+use crate::camera::output::GstCameraImage;
+
+pub(crate) const VIDEO_MATERIAL_SHADER_HANDLE: Handle<Shader> =
+    weak_handle!("a41d93f7-56c2-4e0b-b8d4-7f3c2e91a605");
+
+/// A [`StandardMaterial`] showing camera frames on a mesh
+///
+/// Lighting, `unlit` and `uv_transform` are set on the base material, the texture is kept
+/// up to date by a [`VideoMaterialSource`] on the mesh entity. Use an alpha mode other than
+/// opaque on the base material when keying.
+pub type VideoMaterial = ExtendedMaterial<StandardMaterial, VideoMaterialExtension>;
+
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone, Default)]
+#[uniform(100, ChromaKeyUniform)]
+pub struct VideoMaterialExtension {
+    pub chroma_key: Option<ChromaKey>,
+}
+
+impl AsBindGroupShaderType<ChromaKeyUniform> for VideoMaterialExtension {
+    fn as_bind_group_shader_type(&self, _images: &RenderAssets<GpuImage>) -> ChromaKeyUniform {
+        ChromaKey::uniform(self.chroma_key.as_ref())
+    }
+}
+
+impl MaterialExtension for VideoMaterialExtension {
+    fn fragment_shader() -> ShaderRef {
+        VIDEO_MATERIAL_SHADER_HANDLE.into()
+    }
+
+    fn deferred_fragment_shader() -> ShaderRef {
+        VIDEO_MATERIAL_SHADER_HANDLE.into()
+    }
+}
+
+/// Show the frames of a [`GstCamera`](crate::camera::GstCamera) with the [`VideoMaterial`] of this mesh
+///
+/// `source` is the entity holding the `GstCamera`.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+#[reflect(Component)]
+pub struct VideoMaterialSource {
+    pub source: Entity,
+}
+
+/// point video materials at their camera image, and touch them when a new frame arrives
+///
+/// Material bind groups keep the texture they were created with, so they are rebuilt per frame.
+pub(crate) fn update_video_materials(
+    mut image_events: EventReader<AssetEvent<Image>>,
+    meshes: Query<(&VideoMaterialSource, &MeshMaterial3d<VideoMaterial>)>,
+    camera_images: Query<&GstCameraImage>,
+    mut materials: ResMut<Assets<VideoMaterial>>,
+) {
+    let modified: HashSet<AssetId<Image>> = image_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+
+    for (video, material) in &meshes {
+        let Ok(image) = camera_images.get(video.source) else {
+            continue;
+        };
+        let Some(current) = materials.get(&material.0) else {
+            continue;
+        };
+        let current = current.base.base_color_texture.as_ref().map(Handle::id);
+        if current == Some(image.id()) && !modified.contains(&image.id()) {
+            continue;
+        }
+        if let Some(material) = materials.get_mut(&material.0) {
+            material.base.base_color_texture = Some(image.0.clone());
+        }
+    }
+}
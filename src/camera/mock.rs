@@ -0,0 +1,378 @@
+use std::borrow::Cow;
+use std::fmt::{Debug, Display, Formatter};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use gstreamer::prelude::*;
+use gstreamer::{ClockTime, Element};
+use gstreamer_app::{AppSrc, AppSrcCallbacks};
+use image::codecs::gif::GifDecoder;
+use image::imageops::FilterType;
+use image::{AnimationDecoder, DynamicImage, ImageFormat, RgbImage};
+
+use crate::camera::source::{GstCameraFailed, autoplay, insert_camera};
+use crate::camera::{GstCamera, GstCameraOptions, format_caps};
+use crate::error::BevyGstError;
+use crate::types::{CameraFormat, CameraInfo, FrameFormat, Orientation};
+
+type FrameFn = dyn Fn(u64) -> RgbImage + Send + Sync;
+
+/// Where a [`MockCamera`] takes its frames from
+#[derive(Clone)]
+pub enum MockFrames {
+    /// a still image shown on every frame
+    Image(PathBuf),
+    /// the images of a directory in file name order, scaled to the size of the first
+    ImageSequence(PathBuf),
+    /// an animated GIF, played with its own frame delays
+    Gif(PathBuf),
+    /// frames drawn from the frame number, scaled to `width` x `height`
+    Generator {
+        width: u32,
+        height: u32,
+        frame: Arc<FrameFn>,
+    },
+}
+
+impl Display for MockFrames {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MockFrames::Image(path) => write!(f, "image {}", path.display()),
+            MockFrames::ImageSequence(path) => write!(f, "image sequence {}", path.display()),
+            MockFrames::Gif(path) => write!(f, "gif {}", path.display()),
+            MockFrames::Generator { width, height, .. } => {
+                write!(f, "generator {width}x{height}")
+            }
+        }
+    }
+}
+
+impl Debug for MockFrames {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MockFrames({self})")
+    }
+}
+
+/// Virtual camera playing images instead of a device, for development and CI without webcams
+///
+/// Inserting it opens a [`GstCamera`] on the entity like a [`GstCameraSource`](crate::camera::GstCameraSource),
+/// frames arrive as RGB at a fixed rate and in the same order on every run.
+#[derive(Component, Clone, Debug)]
+pub struct MockCamera {
+    pub frames: MockFrames,
+    /// frames per second, GIFs keep their own frame delays
+    pub fps: u32,
+    /// start over after the last frame, otherwise the stream ends
+    pub looping: bool,
+    pub orientation: Orientation,
+    /// start streaming as soon as the camera is opened
+    pub autoplay: bool,
+    /// the name reported by [`CameraInfo::human_name`]
+    pub name: String,
+}
+
+impl MockCamera {
+    pub fn new(frames: MockFrames) -> Self {
+        Self {
+            frames,
+            fps: 30,
+            looping: true,
+            orientation: Orientation::default(),
+            autoplay: true,
+            name: "Mock camera".to_string(),
+        }
+    }
+
+    pub fn image(path: impl Into<PathBuf>) -> Self {
+        Self::new(MockFrames::Image(path.into()))
+    }
+
+    pub fn image_sequence(dir: impl Into<PathBuf>) -> Self {
+        Self::new(MockFrames::ImageSequence(dir.into()))
+    }
+
+    pub fn gif(path: impl Into<PathBuf>) -> Self {
+        Self::new(MockFrames::Gif(path.into()))
+    }
+
+    /// frames drawn by `frame` from the frame number, starting at 0
+    pub fn from_fn(
+        width: u32,
+        height: u32,
+        frame: impl Fn(u64) -> RgbImage + Send + Sync + 'static,
+    ) -> Self {
+        Self::new(MockFrames::Generator {
+            width,
+            height,
+            frame: Arc::new(frame),
+        })
+    }
+
+    /// load the frames and open a camera already streaming them, [`MockCamera::autoplay`] is not
+    /// applied here, stop it with [`GstCamera::stop_stream`]
+    ///
+    /// The frame size is fixed, changing the camera format to another resolution fails.
+    pub fn open(&self) -> Result<GstCamera, BevyGstError> {
+        if self.fps == 0 {
+            return Err(BevyGstError::MockCameraError(
+                self.frames.to_string(),
+                "Frame rate must not be 0".to_string(),
+            ));
+        }
+
+        let feed = Arc::new(MockFeed::load(self)?);
+        let format = CameraFormat::new_from(feed.width, feed.height, FrameFormat::RAWRGB, self.fps);
+        // identity paces the frames to their timestamps, the appsink does not sync
+        let source = format!(
+            "appsrc name=source is-live=true format=time caps=\"{}\" ! identity sync=true",
+            format_caps(format)
+        );
+
+        let options = GstCameraOptions {
+            orientation: self.orientation,
+            ..Default::default()
+        };
+        let mut camera = GstCamera::from_source_with_options(source, Some(format), options)?;
+        camera.camera_info =
+            CameraInfo::new(&self.name, "Mock camera", &self.frames.to_string(), 0);
+        feed.attach(&camera.source()?)?;
+        camera.mock = Some(feed);
+        Ok(camera)
+    }
+}
+
+enum FeedFrames {
+    Decoded(Vec<(RgbImage, Duration)>),
+    Generated(Arc<FrameFn>, Duration),
+}
+
+struct FeedState {
+    frame: u64,
+    pts: ClockTime,
+}
+
+/// the frames of a [`MockCamera`], pushed into its `appsrc` on demand
+pub(crate) struct MockFeed {
+    frames: FeedFrames,
+    width: u32,
+    height: u32,
+    looping: bool,
+    state: Mutex<FeedState>,
+}
+
+impl MockFeed {
+    fn load(mock: &MockCamera) -> Result<Self, BevyGstError> {
+        let frame_duration = Duration::from_secs(1) / mock.fps;
+        let frames = match &mock.frames {
+            MockFrames::Image(path) => vec![(open_image(path)?, frame_duration)],
+            MockFrames::ImageSequence(dir) => load_sequence(dir, frame_duration)?,
+            MockFrames::Gif(path) => load_gif(path, frame_duration)?,
+            MockFrames::Generator {
+                width,
+                height,
+                frame,
+            } => {
+                return Ok(Self {
+                    frames: FeedFrames::Generated(frame.clone(), frame_duration),
+                    width: *width,
+                    height: *height,
+                    // generators are endless
+                    looping: true,
+                    state: Mutex::new(FeedState {
+                        frame: 0,
+                        pts: ClockTime::ZERO,
+                    }),
+                });
+            }
+        };
+
+        let (width, height) = frames[0].0.dimensions();
+        let frames = frames
+            .into_iter()
+            .map(|(image, duration)| (fit(image, width, height), duration))
+            .collect();
+
+        Ok(Self {
+            frames: FeedFrames::Decoded(frames),
+            width,
+            height,
+            // a still image is shown until the camera is stopped
+            looping: mock.looping || matches!(mock.frames, MockFrames::Image(_)),
+            state: Mutex::new(FeedState {
+                frame: 0,
+                pts: ClockTime::ZERO,
+            }),
+        })
+    }
+
+    /// feed the `appsrc` of a new pipeline, continuing with the next frame
+    pub(crate) fn attach(self: &Arc<Self>, source: &Element) -> Result<(), BevyGstError> {
+        let Some(appsrc) = source.downcast_ref::<AppSrc>() else {
+            return Err(BevyGstError::GeneralError(
+                "Mock camera source is no appsrc".to_string(),
+            ));
+        };
+
+        // timestamps restart with the running time of the new pipeline
+        self.state.lock().unwrap().pts = ClockTime::ZERO;
+        let feed = self.clone();
+        appsrc.set_callbacks(
+            AppSrcCallbacks::builder()
+                .need_data(move |appsrc, _| feed.push_frame(appsrc))
+                .build(),
+        );
+        Ok(())
+    }
+
+    fn frame(&self, number: u64) -> Option<(Cow<'_, RgbImage>, Duration)> {
+        match &self.frames {
+            FeedFrames::Decoded(frames) => {
+                let count = frames.len() as u64;
+                let index = if number < count {
+                    number
+                } else if self.looping {
+                    number % count
+                } else {
+                    return None;
+                };
+                let (image, duration) = &frames[index as usize];
+                Some((Cow::Borrowed(image), *duration))
+            }
+            FeedFrames::Generated(frame, duration) => Some((
+                Cow::Owned(fit(frame(number), self.width, self.height)),
+                *duration,
+            )),
+        }
+    }
+
+    fn push_frame(&self, appsrc: &AppSrc) {
+        let mut state = self.state.lock().unwrap();
+        let Some((image, duration)) = self.frame(state.frame) else {
+            let _ = appsrc.end_of_stream();
+            return;
+        };
+
+        // rows of raw RGB video are padded to 4 bytes
+        let row_len = self.width as usize * 3;
+        let stride = row_len.next_multiple_of(4);
+        let mut data = vec![0_u8; stride * self.height as usize];
+        for (row, pixels) in data.chunks_mut(stride).zip(image.chunks(row_len)) {
+            row[..row_len].copy_from_slice(pixels);
+        }
+
+        let duration = ClockTime::from_nseconds(duration.as_nanos() as u64);
+        let mut buffer = gstreamer::Buffer::from_mut_slice(data);
+        if let Some(buffer) = buffer.get_mut() {
+            buffer.set_pts(state.pts);
+            buffer.set_duration(duration);
+        }
+        state.frame += 1;
+        state.pts += duration;
+
+        // fails while flushing when the camera is stopped
+        let _ = appsrc.push_buffer(buffer);
+    }
+}
+
+fn open_image(path: &Path) -> Result<RgbImage, BevyGstError> {
+    match image::open(path) {
+        Ok(image) => Ok(image.into_rgb8()),
+        Err(why) => Err(BevyGstError::MockCameraError(
+            path.display().to_string(),
+            why.to_string(),
+        )),
+    }
+}
+
+fn load_sequence(
+    dir: &Path,
+    frame_duration: Duration,
+) -> Result<Vec<(RgbImage, Duration)>, BevyGstError> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(why) => {
+            return Err(BevyGstError::MockCameraError(
+                dir.display().to_string(),
+                why.to_string(),
+            ));
+        }
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && ImageFormat::from_path(path).is_ok())
+        .collect();
+    paths.sort();
+    if paths.is_empty() {
+        return Err(BevyGstError::MockCameraError(
+            dir.display().to_string(),
+            "Directory contains no images".to_string(),
+        ));
+    }
+
+    paths
+        .iter()
+        .map(|path| Ok((open_image(path)?, frame_duration)))
+        .collect()
+}
+
+fn load_gif(
+    path: &Path,
+    frame_duration: Duration,
+) -> Result<Vec<(RgbImage, Duration)>, BevyGstError> {
+    let error = |why: String| BevyGstError::MockCameraError(path.display().to_string(), why);
+    let file = File::open(path).map_err(|why| error(why.to_string()))?;
+    let decoder = GifDecoder::new(BufReader::new(file)).map_err(|why| error(why.to_string()))?;
+    let frames = decoder
+        .into_frames()
+        .collect_frames()
+        .map_err(|why| error(why.to_string()))?;
+    if frames.is_empty() {
+        return Err(error("GIF contains no frames".to_string()));
+    }
+
+    Ok(frames
+        .into_iter()
+        .map(|frame| {
+            let delay = Duration::from(frame.delay());
+            let image = DynamicImage::ImageRgba8(frame.into_buffer()).into_rgb8();
+            // a delay of 0 is shown at the camera rate, as browsers do
+            let delay = if delay.is_zero() {
+                frame_duration
+            } else {
+                delay
+            };
+            (image, delay)
+        })
+        .collect())
+}
+
+/// scale `image` to the frame size of the feed
+fn fit(image: RgbImage, width: u32, height: u32) -> RgbImage {
+    if image.dimensions() == (width, height) {
+        image
+    } else {
+        image::imageops::resize(&image, width, height, FilterType::Triangle)
+    }
+}
+
+pub(crate) fn open_mock_camera(
+    trigger: Trigger<OnInsert, MockCamera>,
+    mocks: Query<&MockCamera>,
+    mut commands: Commands,
+    mut failed: EventWriter<GstCameraFailed>,
+) {
+    let entity = trigger.target();
+    let Ok(mock) = mocks.get(entity) else {
+        return;
+    };
+
+    let camera = mock
+        .open()
+        .and_then(|camera| autoplay(camera, mock.autoplay));
+    insert_camera(&mut commands, &mut failed, entity, &mock.name, camera);
+}
@@ -0,0 +1,70 @@
+use bevy::asset::RenderAssetUsages;
+use bevy::prelude::*;
+use image::DynamicImage;
+
+use crate::camera::GstCamera;
+use crate::camera::source::{GstCameraError, GstCameraFailed};
+
+/// The latest frame of a [`GstCamera`] as a bevy image, inserted by the plugin
+///
+/// Use the handle to show the camera on a background, a material or in UI.
+#[derive(Component, Clone, Debug, Deref, PartialEq, Eq)]
+pub struct GstCameraImage(pub Handle<Image>);
+
+/// copy new camera frames into the camera images and report stream errors
+///
+/// Frames are peeked, [`GstCamera::frame`] and [`GstCamera::poll_frame`] still return them.
+pub(crate) fn update_camera_images(
+    mut commands: Commands,
+    mut cameras: Query<(
+        Entity,
+        &mut GstCamera,
+        Option<&GstCameraImage>,
+        Has<GstCameraError>,
+    )>,
+    mut images: ResMut<Assets<Image>>,
+    mut failed: EventWriter<GstCameraFailed>,
+) {
+    for (entity, mut camera, camera_image, has_error) in &mut cameras {
+        match camera.poll_bus() {
+            // the camera keeps the error, report it once
+            Err(error) if !has_error => {
+                warn!(
+                    "camera {} failed: {error}",
+                    camera.camera_info().human_name()
+                );
+                commands
+                    .entity(entity)
+                    .insert(GstCameraError(error.clone()));
+                failed.write(GstCameraFailed { entity, error });
+                continue;
+            }
+            Err(_) => continue,
+            Ok(()) if has_error => {
+                commands.entity(entity).remove::<GstCameraError>();
+            }
+            Ok(()) => {}
+        }
+
+        let Some(frame) = camera.peek_new_frame() else {
+            continue;
+        };
+
+        // RGBA sRGB, the render world has no RGB texture format, kept in the main world so the
+        // asset and its handle are updated in place
+        let image = Image::from_dynamic(
+            DynamicImage::ImageRgb8(frame),
+            true,
+            RenderAssetUsages::default(),
+        );
+
+        match camera_image.and_then(|handle| images.get_mut(&handle.0)) {
+            Some(existing) => *existing = image,
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(GstCameraImage(images.add(image)));
+            }
+        }
+    }
+}
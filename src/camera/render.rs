@@ -0,0 +1,145 @@
+use bevy::asset::{AssetEvents, load_internal_asset};
+#[cfg(feature = "background")]
+use bevy::core_pipeline::core_2d::graph::{Core2d, Node2d};
+#[cfg(feature = "background")]
+use bevy::core_pipeline::core_3d::graph::{Core3d, Node3d};
+use bevy::prelude::*;
+use bevy::render::RenderApp;
+#[cfg(feature = "background")]
+use bevy::render::render_graph::{RenderGraphApp, ViewNodeRunner};
+#[cfg(feature = "background")]
+use bevy::render::render_resource::SpecializedRenderPipelines;
+#[cfg(feature = "background")]
+use bevy::render::{ExtractSchedule, Render, RenderSet};
+
+#[cfg(feature = "background")]
+use crate::camera::background::*;
+use crate::camera::chroma_key::{self, ChromaKey};
+#[cfg(feature = "background")]
+use crate::camera::grading::GstColorGrading;
+use crate::camera::material::{self, VideoMaterial, VideoMaterialSource};
+
+/// Draw camera feeds as view backgrounds and on meshes with [`VideoMaterial`]
+///
+/// Does nothing but register types when the app has no `RenderApp`.
+/// Backgrounds need the `background` feature.
+pub struct CameraRenderPlugin;
+
+impl Plugin for CameraRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<ChromaKey>()
+            .register_type::<VideoMaterialSource>();
+        #[cfg(feature = "background")]
+        app.register_type::<BackgroundFit>()
+            .register_type::<BackgroundComposite>()
+            .register_type::<BackgroundShader>()
+            .register_type::<CameraBackground>()
+            .register_type::<GstColorGrading>();
+
+        if app.get_sub_app(RenderApp).is_none() {
+            return;
+        }
+
+        load_internal_asset!(
+            app,
+            chroma_key::CHROMA_KEY_SHADER_HANDLE,
+            "chroma_key.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            material::VIDEO_MATERIAL_SHADER_HANDLE,
+            "video_material.wgsl",
+            Shader::from_wgsl
+        );
+
+        app.add_plugins(MaterialPlugin::<VideoMaterial>::default())
+            .add_systems(
+                PostUpdate,
+                material::update_video_materials.after(AssetEvents),
+            );
+
+        #[cfg(feature = "background")]
+        build_backgrounds(app);
+    }
+
+    #[cfg_attr(not(feature = "background"), allow(unused_variables))]
+    fn finish(&self, app: &mut App) {
+        #[cfg(feature = "background")]
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<BackgroundPipeline>();
+        }
+    }
+}
+
+/// draw backgrounds before the opaque pass and overlays after the main pass
+#[cfg(feature = "background")]
+fn build_backgrounds(app: &mut App) {
+    load_internal_asset!(
+        app,
+        BACKGROUND_SHADER_HANDLE,
+        "shader.wgsl",
+        Shader::from_wgsl
+    );
+    load_internal_asset!(
+        app,
+        BACKGROUND_IMPORT_SHADER_HANDLE,
+        "background.wgsl",
+        Shader::from_wgsl
+    );
+
+    let render_app = app.sub_app_mut(RenderApp);
+    render_app
+        .init_resource::<BackgroundUniforms>()
+        .init_resource::<SpecializedRenderPipelines<BackgroundPipeline>>()
+        .add_systems(ExtractSchedule, extract_camera_backgrounds)
+        .add_systems(
+            Render,
+            (
+                prepare_background_pipelines.in_set(RenderSet::PrepareBindGroups),
+                prepare_backgrounds.in_set(RenderSet::PrepareBindGroups),
+            ),
+        )
+        .add_render_graph_node::<ViewNodeRunner<BackgroundNode>>(Core2d, BackgroundNodeLabel)
+        .add_render_graph_edges(
+            Core2d,
+            (
+                Node2d::StartMainPass,
+                BackgroundNodeLabel,
+                Node2d::MainOpaquePass,
+            ),
+        )
+        .add_render_graph_node::<ViewNodeRunner<BackgroundOverlayNode>>(
+            Core2d,
+            BackgroundOverlayNodeLabel,
+        )
+        .add_render_graph_edges(
+            Core2d,
+            (
+                Node2d::EndMainPass,
+                BackgroundOverlayNodeLabel,
+                Node2d::Tonemapping,
+            ),
+        )
+        .add_render_graph_node::<ViewNodeRunner<BackgroundNode>>(Core3d, BackgroundNodeLabel)
+        .add_render_graph_edges(
+            Core3d,
+            (
+                Node3d::StartMainPass,
+                BackgroundNodeLabel,
+                Node3d::MainOpaquePass,
+            ),
+        )
+        .add_render_graph_node::<ViewNodeRunner<BackgroundOverlayNode>>(
+            Core3d,
+            BackgroundOverlayNodeLabel,
+        )
+        .add_render_graph_edges(
+            Core3d,
+            (
+                Node3d::EndMainPass,
+                BackgroundOverlayNodeLabel,
+                Node3d::Tonemapping,
+            ),
+        );
+}
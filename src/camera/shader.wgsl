@@ -1,3 +1,5 @@
+#import bevy_gstreamer::background::{VertexOutput, composite, frame_uv, sample_frame}
+
 // Vertex shader
 
 struct VertexInput {
@@ -5,11 +7,6 @@ struct VertexInput {
    @location(1) tex_coords: vec2<f32>,
 };
 
-struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,
-    @location(0) tex_coords: vec2<f32>,
-};
-
 @vertex
 fn vs_main(model: VertexInput) -> VertexOutput {
     var out: VertexOutput;
@@ -20,12 +17,7 @@ fn vs_main(model: VertexInput) -> VertexOutput {
 
 // Fragment shader
 
-@group(0) @binding(0)
-var t_diffuse: texture_2d<f32>;
-@group(0) @binding(1)
-var s_diffuse: sampler;
-
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return composite(sample_frame(frame_uv(in.tex_coords)));
 }
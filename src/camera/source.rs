@@ -0,0 +1,135 @@
+use bevy::prelude::*;
+
+use crate::camera::{EncodedPassthrough, GstCamera, GstCameraOptions, MjpegDecoding};
+use crate::convert::Colorimetry;
+use crate::error::BevyGstError;
+use crate::types::{CameraFormat, CameraIndex, Orientation};
+
+/// Declarative camera, the plugin opens a [`GstCamera`] on the entity when this is inserted
+///
+/// Failures are reported with a [`GstCameraError`] component and a [`GstCameraFailed`] event.
+#[derive(Component, Clone, Debug, PartialEq, Reflect)]
+#[reflect(Component)]
+pub struct GstCameraSource {
+    pub device: CameraIndex,
+    /// capture format, the default [`CameraFormat`] when `None`
+    pub format: Option<CameraFormat>,
+    pub orientation: Orientation,
+    /// where MJPEG frames are decoded
+    pub mjpeg_decoding: MjpegDecoding,
+    /// YUV matrix and range replacing the ones of the caps
+    pub colorimetry: Option<Colorimetry>,
+    /// hand out samples before decoding
+    pub encoded_passthrough: EncodedPassthrough,
+    /// start streaming as soon as the camera is opened
+    pub autoplay: bool,
+}
+
+impl Default for GstCameraSource {
+    fn default() -> Self {
+        Self {
+            device: CameraIndex::default(),
+            format: None,
+            orientation: Orientation::default(),
+            mjpeg_decoding: MjpegDecoding::default(),
+            colorimetry: None,
+            encoded_passthrough: EncodedPassthrough::default(),
+            autoplay: true,
+        }
+    }
+}
+
+impl GstCameraSource {
+    /// the options the pipeline of the camera is built with
+    pub fn options(&self) -> GstCameraOptions {
+        GstCameraOptions {
+            orientation: self.orientation,
+            mjpeg_decoding: self.mjpeg_decoding,
+            colorimetry: self.colorimetry,
+            encoded_passthrough: self.encoded_passthrough,
+        }
+    }
+
+    pub fn new(device: impl Into<CameraIndex>, format: Option<CameraFormat>) -> Self {
+        Self {
+            device: device.into(),
+            format,
+            ..Default::default()
+        }
+    }
+}
+
+/// The last error opening or streaming the camera of this entity, removed once it opens or streams again
+#[derive(Component, Clone, Debug, Deref)]
+pub struct GstCameraError(pub BevyGstError);
+
+/// Sent when a [`GstCameraSource`] or [`MockCamera`](crate::camera::MockCamera) could not be opened,
+/// or a camera stream ended or failed
+#[derive(Event, Clone, Debug)]
+pub struct GstCameraFailed {
+    pub entity: Entity,
+    pub error: BevyGstError,
+}
+
+pub(crate) fn open_camera_source(
+    trigger: Trigger<OnInsert, GstCameraSource>,
+    sources: Query<&GstCameraSource>,
+    mut commands: Commands,
+    mut failed: EventWriter<GstCameraFailed>,
+) {
+    let entity = trigger.target();
+    let Ok(source) = sources.get(entity) else {
+        return;
+    };
+
+    let camera = GstCamera::with_options(source.device.clone(), source.format, source.options())
+        .and_then(|camera| autoplay(camera, source.autoplay));
+
+    insert_camera(&mut commands, &mut failed, entity, &source.device, camera);
+}
+
+/// start or pause a freshly opened camera
+pub(crate) fn autoplay(mut camera: GstCamera, autoplay: bool) -> Result<GstCamera, BevyGstError> {
+    if autoplay {
+        camera.open_stream()?;
+    } else {
+        camera.stop_stream()?;
+    }
+    Ok(camera)
+}
+
+/// insert an opened camera on `entity`, or report why it could not be opened
+pub(crate) fn insert_camera(
+    commands: &mut Commands,
+    failed: &mut EventWriter<GstCameraFailed>,
+    entity: Entity,
+    name: &dyn std::fmt::Display,
+    camera: Result<GstCamera, BevyGstError>,
+) {
+    match camera {
+        Ok(camera) => {
+            commands
+                .entity(entity)
+                .insert(camera)
+                .remove::<GstCameraError>();
+        }
+        Err(error) => {
+            warn!("failed to open camera {name}: {error}");
+            commands
+                .entity(entity)
+                .remove::<GstCamera>()
+                .insert(GstCameraError(error.clone()));
+            failed.write(GstCameraFailed { entity, error });
+        }
+    }
+}
+
+/// close the camera opened for a declarative camera component `C`
+pub(crate) fn close_camera_source<C: Component>(
+    trigger: Trigger<OnRemove, C>,
+    mut commands: Commands,
+) {
+    if let Ok(mut entity) = commands.get_entity(trigger.target()) {
+        entity.remove::<(GstCamera, GstCameraError)>();
+    }
+}
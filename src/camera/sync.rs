@@ -0,0 +1,271 @@
+use std::collections::VecDeque;
+
+use gstreamer::ClockTime;
+use image::{ImageBuffer, Rgb};
+
+/// How decoded frames are presented to bevy
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FrameSync {
+    /// present frames as soon as they are decoded or by their timestamps
+    pub mode: SyncMode,
+    /// when several frames became due between two updates, skip to the newest one instead of
+    /// presenting them one per update
+    pub drop_late: bool,
+    /// when no new frame is due, present the last frame again instead of reporting no frame
+    pub repeat: bool,
+    /// maximum number of decoded frames waiting to be presented
+    pub queue_size: usize,
+}
+
+impl Default for FrameSync {
+    fn default() -> Self {
+        Self {
+            mode: SyncMode::Latest,
+            drop_late: true,
+            repeat: true,
+            queue_size: 4,
+        }
+    }
+}
+
+impl FrameSync {
+    /// present frames by their PTS against the pipeline clock, for file or network playback
+    pub fn clock() -> Self {
+        Self {
+            mode: SyncMode::Clock,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SyncMode {
+    /// always present the newest decoded frame, best for live capture
+    #[default]
+    Latest,
+    /// present frames when their running time is reached on the pipeline clock
+    Clock,
+}
+
+/// A decoded frame waiting to be presented
+pub(crate) struct QueuedFrame {
+    pub(crate) image: ImageBuffer<Rgb<u8>, Vec<u8>>,
+    /// running time of the frame, `None` if the buffer had no timestamp
+    pub(crate) running_time: Option<ClockTime>,
+}
+
+/// Frames shared between the appsink streaming thread and bevy
+#[derive(Default)]
+pub(crate) struct FrameQueue {
+    frames: VecDeque<QueuedFrame>,
+    capacity: usize,
+    current: Option<ImageBuffer<Rgb<u8>, Vec<u8>>>,
+    /// frames pushed so far, the sequence number of the newest frame
+    pushed: u64,
+    /// sequence number of `current`
+    current_sequence: u64,
+    /// sequence number of the frame returned by the last `peek_new`
+    peeked: u64,
+}
+
+impl FrameQueue {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            frames: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.frames.len() > self.capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    /// push a decoded frame, dropping the oldest one if the queue is full
+    pub(crate) fn push(&mut self, frame: QueuedFrame) {
+        while self.frames.len() >= self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+        self.pushed += 1;
+    }
+
+    /// drop all queued and presented frames, used when the pipeline is rebuilt or flushed
+    pub(crate) fn clear(&mut self) {
+        self.frames.clear();
+        self.current = None;
+    }
+
+    /// pick the frame to present at `now` according to `sync`
+    ///
+    /// Returns `None` when no frame is available, or when no new frame is due and
+    /// [`FrameSync::repeat`] is off.
+    pub(crate) fn present(
+        &mut self,
+        sync: &FrameSync,
+        now: Option<ClockTime>,
+    ) -> Option<&ImageBuffer<Rgb<u8>, Vec<u8>>> {
+        let due = self.due(sync, now);
+        if due == 0 {
+            return if sync.repeat {
+                self.current.as_ref()
+            } else {
+                None
+            };
+        }
+
+        let skip = if sync.drop_late { due - 1 } else { 0 };
+        self.frames.drain(..skip);
+        self.current_sequence = self.sequence(0);
+        self.current = self.frames.pop_front().map(|frame| frame.image);
+        self.current.as_ref()
+    }
+
+    /// the newest frame due at `now` if it was not peeked yet, leaving the queue as it is
+    ///
+    /// Lets the plugin follow a camera without taking frames from [`FrameQueue::present`].
+    pub(crate) fn peek_new(
+        &mut self,
+        sync: &FrameSync,
+        now: Option<ClockTime>,
+    ) -> Option<&ImageBuffer<Rgb<u8>, Vec<u8>>> {
+        let (sequence, image) = match self.due(sync, now).checked_sub(1) {
+            Some(newest) => (self.sequence(newest), &self.frames[newest].image),
+            None => (self.current_sequence, self.current.as_ref()?),
+        };
+        if sequence == self.peeked {
+            return None;
+        }
+        self.peeked = sequence;
+        Some(image)
+    }
+
+    /// the sequence number of the queued frame at `index`
+    fn sequence(&self, index: usize) -> u64 {
+        self.pushed - self.frames.len() as u64 + index as u64 + 1
+    }
+
+    /// the number of queued frames due at `now`
+    fn due(&self, sync: &FrameSync, now: Option<ClockTime>) -> usize {
+        match sync.mode {
+            SyncMode::Latest => self.frames.len(),
+            SyncMode::Clock => self
+                .frames
+                .iter()
+                .take_while(|frame| match (frame.running_time, now) {
+                    (Some(pts), Some(now)) => pts <= now,
+                    // without timestamps or a clock there is nothing to wait for
+                    _ => true,
+                })
+                .count(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a 1x1 frame filled with `value`, due at `ms` milliseconds of running time
+    fn frame(value: u8, ms: u64) -> QueuedFrame {
+        QueuedFrame {
+            image: ImageBuffer::from_pixel(1, 1, Rgb([value; 3])),
+            running_time: Some(ClockTime::from_mseconds(ms)),
+        }
+    }
+
+    fn presented(queue: &mut FrameQueue, sync: &FrameSync, ms: u64) -> Option<u8> {
+        queue
+            .present(sync, Some(ClockTime::from_mseconds(ms)))
+            .map(|image| image.get_pixel(0, 0)[0])
+    }
+
+    #[test]
+    fn late_frames_are_dropped() {
+        let sync = FrameSync::clock();
+        let mut queue = FrameQueue::new(4);
+        queue.push(frame(1, 10));
+        queue.push(frame(2, 20));
+        queue.push(frame(3, 30));
+
+        assert_eq!(presented(&mut queue, &sync, 25), Some(2));
+        assert_eq!(presented(&mut queue, &sync, 35), Some(3));
+    }
+
+    #[test]
+    fn repeat_presents_the_previous_frame() {
+        let mut queue = FrameQueue::new(4);
+        queue.push(frame(1, 10));
+
+        let sync = FrameSync::default();
+        assert_eq!(presented(&mut queue, &sync, 10), Some(1));
+        assert_eq!(presented(&mut queue, &sync, 20), Some(1));
+
+        let sync = FrameSync {
+            repeat: false,
+            ..sync
+        };
+        assert_eq!(presented(&mut queue, &sync, 30), None);
+    }
+
+    #[test]
+    fn frames_are_presented_in_running_time_order() {
+        let sync = FrameSync {
+            drop_late: false,
+            ..FrameSync::clock()
+        };
+        let mut queue = FrameQueue::new(4);
+        queue.push(frame(1, 10));
+        queue.push(frame(2, 20));
+        queue.push(frame(3, 30));
+
+        // frames not due yet are held back
+        assert_eq!(presented(&mut queue, &sync, 15), Some(1));
+        assert_eq!(presented(&mut queue, &sync, 15), Some(1));
+        // several due frames come out one per update
+        assert_eq!(presented(&mut queue, &sync, 40), Some(2));
+        assert_eq!(presented(&mut queue, &sync, 40), Some(3));
+    }
+
+    #[test]
+    fn peeking_leaves_frames_to_present() {
+        let sync = FrameSync::clock();
+        let mut queue = FrameQueue::new(4);
+        queue.push(frame(1, 10));
+        queue.push(frame(2, 20));
+
+        let peek = |queue: &mut FrameQueue, ms| {
+            queue
+                .peek_new(&sync, Some(ClockTime::from_mseconds(ms)))
+                .map(|image| image.get_pixel(0, 0)[0])
+        };
+        assert_eq!(peek(&mut queue, 15), Some(1));
+        assert_eq!(peek(&mut queue, 15), None);
+        assert_eq!(peek(&mut queue, 25), Some(2));
+
+        let sync = FrameSync {
+            drop_late: false,
+            ..sync
+        };
+        assert_eq!(presented(&mut queue, &sync, 25), Some(1));
+        assert_eq!(presented(&mut queue, &sync, 25), Some(2));
+        assert_eq!(peek(&mut queue, 25), None);
+    }
+
+    #[test]
+    fn full_queue_drops_the_oldest_frame() {
+        let mut queue = FrameQueue::new(2);
+        queue.push(frame(1, 10));
+        queue.push(frame(2, 20));
+        queue.push(frame(3, 30));
+
+        let sync = FrameSync {
+            drop_late: false,
+            ..FrameSync::clock()
+        };
+        assert_eq!(presented(&mut queue, &sync, 40), Some(2));
+    }
+}
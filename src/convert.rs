@@ -0,0 +1,531 @@
+//! Conversion of raw camera frames to RGB, RGBA or gray
+//!
+//! Pixels are converted in fixed size blocks. The portable path leaves vectorizing to the
+//! compiler, on x86_64 CPUs with AVX2 the YUV math runs on `std::arch` intrinsics and the rest of
+//! the loops are compiled for AVX2 too, `cargo bench --bench convert` compares both. With the
+//! `parallel` feature bands of rows are converted on the rayon thread pool.
+//!
+//! YUV is converted with the matrix and range of a [`Colorimetry`], in 8 bit fixed point.
+
+use bevy::reflect::Reflect;
+
+use crate::camera::PixelFormat;
+use crate::error::BevyGstError;
+
+/// pixels converted at once, a multiple of the widest vector
+const BLOCK: usize = 32;
+
+/// output bytes per band of rows converted on one thread
+#[cfg(feature = "parallel")]
+const BAND_BYTES: usize = 64 * 1024;
+
+/// One plane of a raw frame
+#[derive(Copy, Clone, Debug)]
+pub struct Plane<'a> {
+    pub data: &'a [u8],
+    /// bytes from the start of one row to the next
+    pub stride: usize,
+}
+
+impl<'a> Plane<'a> {
+    pub fn new(data: &'a [u8], stride: usize) -> Self {
+        Self { data, stride }
+    }
+
+    fn row(&self, y: usize) -> &'a [u8] {
+        &self.data[y * self.stride..]
+    }
+
+    fn check(&self, name: &str, row_len: usize, rows: usize) -> Result<(), BevyGstError> {
+        let needed = match rows {
+            0 => 0,
+            rows => self.stride * (rows - 1) + row_len,
+        };
+        if self.stride < row_len || self.data.len() < needed {
+            return Err(BevyGstError::ConvertFrameError(format!(
+                "{name} plane of {} bytes with stride {} is too small for {rows} rows of {row_len} bytes",
+                self.data.len(),
+                self.stride
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Matrix of the YUV to RGB conversion
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+pub enum ColorMatrix {
+    /// SD video and most webcams at VGA
+    #[default]
+    Bt601,
+    /// HD video
+    Bt709,
+    /// UHD video
+    Bt2020,
+}
+
+/// Range of the luma and chroma values
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+pub enum ColorRange {
+    /// luma in 16..=235 and chroma in 16..=240
+    #[default]
+    Limited,
+    /// all values in 0..=255, like JPEG
+    Full,
+}
+
+/// How YUV values map to RGB
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+pub struct Colorimetry {
+    pub matrix: ColorMatrix,
+    pub range: ColorRange,
+}
+
+impl Colorimetry {
+    pub fn new(matrix: ColorMatrix, range: ColorRange) -> Self {
+        Self { matrix, range }
+    }
+
+    /// the coefficients scaled by 256
+    fn coefficients(&self) -> Coefficients {
+        let [rv, gu, gv, bu] = match (self.matrix, self.range) {
+            (ColorMatrix::Bt601, ColorRange::Limited) => [409, 100, 208, 516],
+            (ColorMatrix::Bt601, ColorRange::Full) => [359, 88, 183, 454],
+            (ColorMatrix::Bt709, ColorRange::Limited) => [459, 55, 136, 541],
+            (ColorMatrix::Bt709, ColorRange::Full) => [403, 48, 120, 475],
+            (ColorMatrix::Bt2020, ColorRange::Limited) => [430, 48, 167, 548],
+            (ColorMatrix::Bt2020, ColorRange::Full) => [377, 42, 146, 482],
+        };
+        let (y_offset, y) = match self.range {
+            ColorRange::Limited => (16, 298),
+            ColorRange::Full => (0, 256),
+        };
+        Coefficients {
+            y_offset,
+            y,
+            rv,
+            gu,
+            gv,
+            bu,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Coefficients {
+    y_offset: i32,
+    y: i32,
+    rv: i32,
+    gu: i32,
+    gv: i32,
+    bu: i32,
+}
+
+/// A raw frame in one of the layouts cameras deliver
+#[derive(Copy, Clone, Debug)]
+pub enum RawFrame<'a> {
+    /// packed 4:2:2, `Y0 U Y1 V`
+    Yuyv(Plane<'a>),
+    /// packed 4:2:2, `U Y0 V Y1`
+    Uyvy(Plane<'a>),
+    /// packed 4:2:2, `Y0 V Y1 U`
+    Yvyu(Plane<'a>),
+    /// 4:2:0, a luma plane and an interleaved `U V` plane
+    Nv12 { y: Plane<'a>, uv: Plane<'a> },
+    /// 4:2:0 like NV12 in little endian 16 bit samples, the 8 high bits are converted
+    P010 { y: Plane<'a>, uv: Plane<'a> },
+    /// 4:2:0, three planes
+    I420 {
+        y: Plane<'a>,
+        u: Plane<'a>,
+        v: Plane<'a>,
+    },
+    /// luma only, replicated into the color channels
+    Gray(Plane<'a>),
+}
+
+impl RawFrame<'_> {
+    fn check(&self, width: usize, height: usize) -> Result<(), BevyGstError> {
+        let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+        match self {
+            RawFrame::Yuyv(plane) | RawFrame::Uyvy(plane) | RawFrame::Yvyu(plane) => {
+                plane.check("packed", chroma_width * 4, height)
+            }
+            RawFrame::Nv12 { y, uv } => {
+                y.check("Y", width, height)?;
+                uv.check("UV", chroma_width * 2, chroma_height)
+            }
+            RawFrame::P010 { y, uv } => {
+                y.check("Y", width * 2, height)?;
+                uv.check("UV", chroma_width * 4, chroma_height)
+            }
+            RawFrame::I420 { y, u, v } => {
+                y.check("Y", width, height)?;
+                u.check("U", chroma_width, chroma_height)?;
+                v.check("V", chroma_width, chroma_height)
+            }
+            RawFrame::Gray(plane) => plane.check("gray", width, height),
+        }
+    }
+
+    /// gather luma and chroma of `n` pixels of row `y` starting at the even column `x`
+    #[inline(always)]
+    fn gather(
+        &self,
+        y: usize,
+        x: usize,
+        n: usize,
+        ys: &mut [u8; BLOCK],
+        us: &mut [u8; BLOCK],
+        vs: &mut [u8; BLOCK],
+    ) {
+        // zipped chunks instead of indexing keep bounds checks out of the loops
+        let pairs = (x + n).div_ceil(2) - x / 2;
+        let chroma = us.chunks_exact_mut(2).zip(vs.chunks_exact_mut(2));
+        match self {
+            RawFrame::Yuyv(plane) | RawFrame::Uyvy(plane) | RawFrame::Yvyu(plane) => {
+                let (y0, y1, u, v) = match self {
+                    RawFrame::Yuyv(_) => (0, 2, 1, 3),
+                    RawFrame::Uyvy(_) => (1, 3, 0, 2),
+                    _ => (0, 2, 3, 1),
+                };
+                let src = &plane.row(y)[x * 2..][..pairs * 4];
+                let lanes = ys.chunks_exact_mut(2).zip(chroma);
+                for (pair, (ys, (us, vs))) in src.chunks_exact(4).zip(lanes) {
+                    ys[0] = pair[y0];
+                    ys[1] = pair[y1];
+                    us.fill(pair[u]);
+                    vs.fill(pair[v]);
+                }
+            }
+            RawFrame::Nv12 { y: luma, uv } => {
+                ys[..n].copy_from_slice(&luma.row(y)[x..][..n]);
+                let src = &uv.row(y / 2)[x..][..pairs * 2];
+                for (uv, (us, vs)) in src.chunks_exact(2).zip(chroma) {
+                    us.fill(uv[0]);
+                    vs.fill(uv[1]);
+                }
+            }
+            RawFrame::P010 { y: luma, uv } => {
+                let src = &luma.row(y)[x * 2..][..n * 2];
+                for (luma, sample) in ys.iter_mut().zip(src.chunks_exact(2)) {
+                    *luma = sample[1];
+                }
+                let src = &uv.row(y / 2)[x * 2..][..pairs * 4];
+                for (uv, (us, vs)) in src.chunks_exact(4).zip(chroma) {
+                    us.fill(uv[1]);
+                    vs.fill(uv[3]);
+                }
+            }
+            RawFrame::I420 { y: luma, u, v } => {
+                ys[..n].copy_from_slice(&luma.row(y)[x..][..n]);
+                let u = &u.row(y / 2)[x / 2..][..pairs];
+                let v = &v.row(y / 2)[x / 2..][..pairs];
+                for ((u, v), (us, vs)) in u.iter().zip(v).zip(chroma) {
+                    us.fill(*u);
+                    vs.fill(*v);
+                }
+            }
+            RawFrame::Gray(plane) => {
+                ys[..n].copy_from_slice(&plane.row(y)[x..][..n]);
+            }
+        }
+    }
+}
+
+/// convert a `width` x `height` frame into tightly packed rows of `format` in `dest`
+///
+/// `colorimetry` is ignored for gray frames.
+pub fn convert(
+    frame: &RawFrame,
+    width: u32,
+    height: u32,
+    dest: &mut [u8],
+    format: PixelFormat,
+    colorimetry: Colorimetry,
+) -> Result<(), BevyGstError> {
+    convert_with(
+        frame,
+        width,
+        height,
+        dest,
+        format,
+        colorimetry,
+        convert_band,
+    )
+}
+
+/// [`convert`] without the AVX2 kernel, to compare both in benchmarks and tests
+#[doc(hidden)]
+pub fn convert_portable(
+    frame: &RawFrame,
+    width: u32,
+    height: u32,
+    dest: &mut [u8],
+    format: PixelFormat,
+    colorimetry: Colorimetry,
+) -> Result<(), BevyGstError> {
+    convert_with(
+        frame,
+        width,
+        height,
+        dest,
+        format,
+        colorimetry,
+        |frame, width, first_row, dest, format, k| {
+            convert_band_portable(frame, width, first_row, dest, format, k);
+        },
+    )
+}
+
+type ConvertBand = fn(&RawFrame, usize, usize, &mut [u8], PixelFormat, &Coefficients);
+
+fn convert_with(
+    frame: &RawFrame,
+    width: u32,
+    height: u32,
+    dest: &mut [u8],
+    format: PixelFormat,
+    colorimetry: Colorimetry,
+    convert_band: ConvertBand,
+) -> Result<(), BevyGstError> {
+    let (width, height) = (width as usize, height as usize);
+    frame.check(width, height)?;
+
+    let row_len = width * format.bytes_per_pixel();
+    if dest.len() != row_len * height {
+        return Err(BevyGstError::ConvertFrameError(format!(
+            "destination of {} bytes does not fit {width}x{height} {format:?}",
+            dest.len()
+        )));
+    }
+    if row_len == 0 {
+        return Ok(());
+    }
+
+    let coefficients = colorimetry.coefficients();
+    for_each_band(dest, row_len, |first_row, band| {
+        convert_band(frame, width, first_row, band, format, &coefficients);
+    });
+    Ok(())
+}
+
+#[cfg(feature = "parallel")]
+fn for_each_band(dest: &mut [u8], row_len: usize, convert: impl Fn(usize, &mut [u8]) + Sync) {
+    use rayon::prelude::*;
+
+    let rows = (BAND_BYTES / row_len).max(1);
+    dest.par_chunks_mut(rows * row_len)
+        .enumerate()
+        .for_each(|(band, dest)| convert(band * rows, dest));
+}
+
+#[cfg(not(feature = "parallel"))]
+fn for_each_band(dest: &mut [u8], _row_len: usize, convert: impl Fn(usize, &mut [u8])) {
+    convert(0, dest);
+}
+
+fn convert_band(
+    frame: &RawFrame,
+    width: usize,
+    first_row: usize,
+    dest: &mut [u8],
+    format: PixelFormat,
+    coefficients: &Coefficients,
+) {
+    #[cfg(target_arch = "x86_64")]
+    if std::is_x86_feature_detected!("avx2") {
+        // SAFETY: the CPU supports AVX2
+        unsafe { avx2::convert_band(frame, width, first_row, dest, format, coefficients) };
+        return;
+    }
+
+    convert_band_portable(frame, width, first_row, dest, format, coefficients);
+}
+
+fn convert_band_portable(
+    frame: &RawFrame,
+    width: usize,
+    first_row: usize,
+    dest: &mut [u8],
+    format: PixelFormat,
+    coefficients: &Coefficients,
+) {
+    convert_rows(
+        frame,
+        width,
+        first_row,
+        dest,
+        format,
+        coefficients,
+        yuv_to_rgb,
+    );
+}
+
+/// the three color channels of a block
+type Channels = ([u8; BLOCK], [u8; BLOCK], [u8; BLOCK]);
+
+/// converts a block of luma and chroma to RGB
+type Kernel = fn(&Coefficients, &[u8; BLOCK], &[u8; BLOCK], &[u8; BLOCK]) -> Channels;
+
+#[inline(always)]
+fn convert_rows(
+    frame: &RawFrame,
+    width: usize,
+    first_row: usize,
+    dest: &mut [u8],
+    format: PixelFormat,
+    coefficients: &Coefficients,
+    kernel: Kernel,
+) {
+    let row_len = width * format.bytes_per_pixel();
+    for (i, row) in dest.chunks_exact_mut(row_len).enumerate() {
+        match format {
+            PixelFormat::Rgb8 => convert_row::<3>(frame, first_row + i, row, coefficients, kernel),
+            PixelFormat::Rgba8 => {
+                convert_row::<4>(frame, first_row + i, row, coefficients, kernel);
+            }
+            PixelFormat::Gray8 => luma_row(frame, first_row + i, row),
+        }
+    }
+}
+
+#[inline(always)]
+fn convert_row<const BPP: usize>(
+    frame: &RawFrame,
+    y: usize,
+    dest: &mut [u8],
+    coefficients: &Coefficients,
+    kernel: Kernel,
+) {
+    let (mut ys, mut us, mut vs) = ([0; BLOCK], [128; BLOCK], [128; BLOCK]);
+
+    for (block, dest) in dest.chunks_mut(BLOCK * BPP).enumerate() {
+        let n = dest.len() / BPP;
+        frame.gather(y, block * BLOCK, n, &mut ys, &mut us, &mut vs);
+        let (r, g, b) = match frame {
+            // gray is full range, it is not expanded like luma
+            RawFrame::Gray(_) => (ys, ys, ys),
+            _ => kernel(coefficients, &ys, &us, &vs),
+        };
+
+        for (pixel, ((r, g), b)) in dest.chunks_exact_mut(BPP).zip(r.iter().zip(&g).zip(&b)) {
+            pixel[0] = *r;
+            pixel[1] = *g;
+            pixel[2] = *b;
+            if BPP == 4 {
+                pixel[3] = 255;
+            }
+        }
+    }
+}
+
+#[inline(always)]
+fn luma_row(frame: &RawFrame, y: usize, dest: &mut [u8]) {
+    let (mut us, mut vs) = ([0; BLOCK], [0; BLOCK]);
+    let mut ys = [0; BLOCK];
+    for (block, dest) in dest.chunks_mut(BLOCK).enumerate() {
+        let n = dest.len();
+        frame.gather(y, block * BLOCK, n, &mut ys, &mut us, &mut vs);
+        dest.copy_from_slice(&ys[..n]);
+    }
+}
+
+/// fixed point conversion, with BT.601 limited range the same math as [`yuyv444_to_rgb`](crate::types::yuyv444_to_rgb)
+#[inline(always)]
+#[allow(clippy::many_single_char_names)]
+fn yuv_to_rgb(k: &Coefficients, ys: &[u8; BLOCK], us: &[u8; BLOCK], vs: &[u8; BLOCK]) -> Channels {
+    let (mut r, mut g, mut b) = ([0; BLOCK], [0; BLOCK], [0; BLOCK]);
+    for i in 0..BLOCK {
+        let c = (i32::from(ys[i]) - k.y_offset) * k.y;
+        let d = i32::from(us[i]) - 128;
+        let e = i32::from(vs[i]) - 128;
+        r[i] = ((c + k.rv * e + 128) >> 8).clamp(0, 255) as u8;
+        g[i] = ((c - k.gu * d - k.gv * e + 128) >> 8).clamp(0, 255) as u8;
+        b[i] = ((c + k.bu * d + 128) >> 8).clamp(0, 255) as u8;
+    }
+    (r, g, b)
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    use super::{BLOCK, Channels, Coefficients, PixelFormat, RawFrame, convert_rows};
+
+    /// the portable loops compiled for AVX2 around the intrinsics kernel
+    #[target_feature(enable = "avx2")]
+    pub(super) fn convert_band(
+        frame: &RawFrame,
+        width: usize,
+        first_row: usize,
+        dest: &mut [u8],
+        format: PixelFormat,
+        coefficients: &Coefficients,
+    ) {
+        convert_rows(
+            frame,
+            width,
+            first_row,
+            dest,
+            format,
+            coefficients,
+            // the closure inherits AVX2 from `convert_band`
+            |k, ys, us, vs| yuv_to_rgb(k, ys, us, vs),
+        );
+    }
+
+    /// [`super::yuv_to_rgb`] on eight pixels per vector, with the same rounding and clamping
+    #[target_feature(enable = "avx2")]
+    #[allow(clippy::many_single_char_names)]
+    fn yuv_to_rgb(
+        k: &Coefficients,
+        ys: &[u8; BLOCK],
+        us: &[u8; BLOCK],
+        vs: &[u8; BLOCK],
+    ) -> Channels {
+        let y_offset = _mm256_set1_epi32(k.y_offset);
+        let ky = _mm256_set1_epi32(k.y);
+        let (rv, gu) = (_mm256_set1_epi32(k.rv), _mm256_set1_epi32(k.gu));
+        let (gv, bu) = (_mm256_set1_epi32(k.gv), _mm256_set1_epi32(k.bu));
+        let (round, bias) = (_mm256_set1_epi32(128), _mm256_set1_epi32(128));
+
+        // eight pixels widened to 32 bit lanes
+        let widen = |values: &[u8; BLOCK], group: usize| {
+            // SAFETY: the group's eight bytes are within the block
+            _mm256_cvtepu8_epi32(unsafe { _mm_loadl_epi64(values.as_ptr().add(group * 8).cast()) })
+        };
+        let (mut r, mut g, mut b) = (
+            [_mm256_setzero_si256(); 4],
+            [_mm256_setzero_si256(); 4],
+            [_mm256_setzero_si256(); 4],
+        );
+        for group in 0..BLOCK / 8 {
+            let c = _mm256_mullo_epi32(_mm256_sub_epi32(widen(ys, group), y_offset), ky);
+            let c = _mm256_add_epi32(c, round);
+            let d = _mm256_sub_epi32(widen(us, group), bias);
+            let e = _mm256_sub_epi32(widen(vs, group), bias);
+            r[group] = _mm256_srai_epi32::<8>(_mm256_add_epi32(c, _mm256_mullo_epi32(rv, e)));
+            g[group] = _mm256_srai_epi32::<8>(_mm256_sub_epi32(
+                _mm256_sub_epi32(c, _mm256_mullo_epi32(gu, d)),
+                _mm256_mullo_epi32(gv, e),
+            ));
+            b[group] = _mm256_srai_epi32::<8>(_mm256_add_epi32(c, _mm256_mullo_epi32(bu, d)));
+        }
+        (narrow(r), narrow(g), narrow(b))
+    }
+
+    /// saturate four vectors of eight 32 bit lanes to 32 bytes in order, clamping to 0..=255
+    #[target_feature(enable = "avx2")]
+    fn narrow(lanes: [__m256i; 4]) -> [u8; BLOCK] {
+        // the packs work per 128 bit half, the permute restores the pixel order
+        let low = _mm256_packs_epi32(lanes[0], lanes[1]);
+        let high = _mm256_packs_epi32(lanes[2], lanes[3]);
+        let bytes = _mm256_packus_epi16(low, high);
+        let bytes = _mm256_permutevar8x32_epi32(bytes, _mm256_setr_epi32(0, 4, 1, 5, 2, 6, 3, 7));
+        let mut out = [0; BLOCK];
+        // SAFETY: `out` holds the 32 bytes of one vector
+        unsafe { _mm256_storeu_si256(out.as_mut_ptr().cast(), bytes) };
+        out
+    }
+}
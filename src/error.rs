@@ -19,12 +19,26 @@ pub enum BevyGstError {
         destination: String,
         error: String,
     },
+    #[error("Could not convert frame: {0}")]
+    ConvertFrameError(String),
     #[error("Could not open device stream: {0}")]
     OpenStreamError(String),
     #[error("Could not stop device stream: {0}")]
     StreamShutdownError(String),
     #[error("Could not get device property {property}: {error}")]
     GetPropertyError { property: String, error: String },
+    #[error("Could not set device property {property} to {value}: {error}")]
+    SetPropertyError {
+        property: String,
+        value: String,
+        error: String,
+    },
+    #[error("Could not load or save camera config {0}: {1}")]
+    ConfigError(String, String),
+    #[error("Could not load LUT {0}: {1}")]
+    LutError(String, String),
+    #[error("Could not load mock camera frames {0}: {1}")]
+    MockCameraError(String, String),
     #[error("This operation is not implemented yet: {0}")]
     NotImplementedError(String),
 }
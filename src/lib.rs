@@ -1,13 +1,21 @@
 use bevy::app::{App, Plugin};
 
 pub mod camera;
+pub mod convert;
 pub mod error;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod types;
 
+/// Capture and rendering, for headless apps add [`camera::WebCameraPlugin`] alone
+///
+/// Rendering is left out without the `render` feature.
 pub struct GstreamerPlugin;
 
 impl Plugin for GstreamerPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(camera::WebCameraPlugin);
+        #[cfg(feature = "render")]
+        app.add_plugins(camera::CameraRenderPlugin);
     }
 }
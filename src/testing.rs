@@ -0,0 +1,138 @@
+//! Helpers to test capture without camera hardware
+//!
+//! Cameras are built from `videotestsrc` or `appsrc` and run in a headless [`App`].
+
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use gstreamer::prelude::*;
+use gstreamer_app::AppSrc;
+use image::{ImageBuffer, Rgb};
+
+use crate::camera::{GstCamera, WebCameraPlugin, format_caps};
+use crate::error::BevyGstError;
+use crate::types::{CameraFormat, FrameFormat};
+
+/// `videotestsrc` patterns with known pixel values
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TestPattern {
+    /// SMPTE color bars
+    Smpte,
+    /// one color, as `0xAARRGGBB`
+    SolidColor(u32),
+    Black,
+    White,
+}
+
+impl TestPattern {
+    fn properties(&self) -> String {
+        match self {
+            TestPattern::Smpte => "pattern=smpte".to_string(),
+            TestPattern::SolidColor(argb) => {
+                format!("pattern=solid-color foreground-color={argb:#010x}")
+            }
+            TestPattern::Black => "pattern=black".to_string(),
+            TestPattern::White => "pattern=white".to_string(),
+        }
+    }
+}
+
+/// a source description for [`GstCamera::from_source`] producing `pattern` in `format`
+///
+/// Compressed formats need their encoder installed, `x264enc` and `x265enc` for H.264 and H.265.
+pub fn test_source(pattern: TestPattern, format: FrameFormat) -> String {
+    let source = format!(
+        "videotestsrc name=source is-live=true {}",
+        pattern.properties()
+    );
+    // videotestsrc only produces raw video
+    let encoder = match format {
+        FrameFormat::MJPEG => "jpegenc quality=100",
+        FrameFormat::H264 => "x264enc tune=zerolatency speed-preset=ultrafast",
+        FrameFormat::H265 => "x265enc tune=zerolatency speed-preset=ultrafast",
+        FrameFormat::VP8 => "vp8enc deadline=1",
+        FrameFormat::VP9 => "vp9enc deadline=1",
+        _ => return source,
+    };
+    format!("{source} ! {encoder}")
+}
+
+/// a camera showing `pattern`
+pub fn test_camera(pattern: TestPattern, format: CameraFormat) -> Result<GstCamera, BevyGstError> {
+    GstCamera::from_source(test_source(pattern, format.format()), Some(format))
+}
+
+/// a camera showing the frames pushed to its [`app_source`]
+///
+/// Frames are raw bytes in `format`, rows padded to 4 bytes like gstreamer raw video.
+pub fn appsrc_camera(format: CameraFormat) -> Result<GstCamera, BevyGstError> {
+    let source = format!(
+        "appsrc name=source is-live=true format=time do-timestamp=true caps=\"{}\"",
+        format_caps(format)
+    );
+    GstCamera::from_source(source, Some(format))
+}
+
+/// the `appsrc` of an [`appsrc_camera`], replaced whenever the camera pipeline is rebuilt
+pub fn app_source(camera: &GstCamera) -> Result<AppSrc, BevyGstError> {
+    match camera.source()?.dynamic_cast::<AppSrc>() {
+        Ok(appsrc) => Ok(appsrc),
+        Err(_) => Err(BevyGstError::GeneralError(
+            "Camera source is no appsrc".to_string(),
+        )),
+    }
+}
+
+/// push one frame to an [`appsrc_camera`]
+pub fn push_frame(camera: &GstCamera, data: Vec<u8>) -> Result<(), BevyGstError> {
+    match app_source(camera)?.push_buffer(gstreamer::Buffer::from_mut_slice(data)) {
+        Ok(_) => Ok(()),
+        Err(why) => Err(BevyGstError::GeneralError(format!(
+            "Failed to push frame: {why}"
+        ))),
+    }
+}
+
+/// poll `camera` until a frame arrives
+pub fn wait_for_frame(
+    camera: &mut GstCamera,
+    timeout: Duration,
+) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, BevyGstError> {
+    let start = Instant::now();
+    loop {
+        if let Some(frame) = camera.poll_frame()? {
+            return Ok(frame);
+        }
+        if start.elapsed() > timeout {
+            return Err(BevyGstError::ReadFrameError(format!(
+                "No frame within {timeout:?}"
+            )));
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+/// an app with capture and image assets, but no window or renderer
+pub fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), WebCameraPlugin))
+        .init_asset::<Image>();
+    app
+}
+
+/// update `app` until `condition` holds, false on timeout
+pub fn update_until(
+    app: &mut App,
+    timeout: Duration,
+    mut condition: impl FnMut(&mut World) -> bool,
+) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        app.update();
+        if condition(app.world_mut()) {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    false
+}
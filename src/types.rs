@@ -1,8 +1,18 @@
+use crate::camera::PixelFormat;
+use crate::convert::{Colorimetry, Plane, RawFrame, convert};
 use crate::error::BevyGstError;
+use bevy::reflect::Reflect;
+use image::{ImageBuffer, Rgb, imageops};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
 use std::str::FromStr;
 
-#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+#[derive(
+    Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize, Reflect,
+)]
 pub struct Resolution {
     pub width_x: u32,
     pub height_y: u32,
@@ -17,13 +27,44 @@ impl Resolution {
     }
 }
 
-#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+#[derive(
+    Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize, Reflect,
+)]
 pub enum FrameFormat {
     MJPEG,
     YUYV,
+    /// packed 4:2:2 with chroma first, common on capture cards
+    UYVY,
+    /// packed 4:2:2 with V before U
+    YVYU,
     NV12,
+    /// 10 bit 4:2:0 in 16 bit samples like NV12, from HDR sources
+    P010,
     GRAY,
     RAWRGB,
+    /// compressed streams decoded by gstreamer, for modes too large for raw USB 2 transfers
+    H264,
+    H265,
+    VP8,
+    VP9,
+}
+
+impl FrameFormat {
+    /// every format in declaration order
+    pub const ALL: [FrameFormat; 12] = [
+        FrameFormat::MJPEG,
+        FrameFormat::YUYV,
+        FrameFormat::UYVY,
+        FrameFormat::YVYU,
+        FrameFormat::NV12,
+        FrameFormat::P010,
+        FrameFormat::GRAY,
+        FrameFormat::RAWRGB,
+        FrameFormat::H264,
+        FrameFormat::H265,
+        FrameFormat::VP8,
+        FrameFormat::VP9,
+    ];
 }
 
 impl Display for FrameFormat {
@@ -44,6 +85,27 @@ impl Display for FrameFormat {
             FrameFormat::NV12 => {
                 write!(f, "NV12")
             }
+            FrameFormat::UYVY => {
+                write!(f, "UYVY")
+            }
+            FrameFormat::YVYU => {
+                write!(f, "YVYU")
+            }
+            FrameFormat::P010 => {
+                write!(f, "P010")
+            }
+            FrameFormat::H264 => {
+                write!(f, "H264")
+            }
+            FrameFormat::H265 => {
+                write!(f, "H265")
+            }
+            FrameFormat::VP8 => {
+                write!(f, "VP8")
+            }
+            FrameFormat::VP9 => {
+                write!(f, "VP9")
+            }
         }
     }
 }
@@ -58,6 +120,13 @@ impl FromStr for FrameFormat {
             "GRAY" => Ok(FrameFormat::GRAY),
             "RAWRGB" => Ok(FrameFormat::RAWRGB),
             "NV12" => Ok(FrameFormat::NV12),
+            "UYVY" => Ok(FrameFormat::UYVY),
+            "YVYU" => Ok(FrameFormat::YVYU),
+            "P010" => Ok(FrameFormat::P010),
+            "H264" => Ok(FrameFormat::H264),
+            "H265" => Ok(FrameFormat::H265),
+            "VP8" => Ok(FrameFormat::VP8),
+            "VP9" => Ok(FrameFormat::VP9),
             _ => Err(BevyGstError::StructureError {
                 structure: "FrameFormat".to_string(),
                 error: format!("No match for {s}"),
@@ -66,7 +135,9 @@ impl FromStr for FrameFormat {
     }
 }
 
-#[derive(Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+#[derive(
+    Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize, Reflect,
+)]
 pub struct CameraFormat {
     resolution: Resolution,
     format: FrameFormat,
@@ -131,12 +202,16 @@ impl CameraFormat {
     }
 }
 
-#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd)]
+#[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Serialize, Deserialize, Reflect)]
 pub struct CameraInfo {
     human_name: String,
     description: String,
     misc: String,
     index: usize,
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    id: Option<String>,
 }
 
 impl CameraInfo {
@@ -146,20 +221,326 @@ impl CameraInfo {
             description: description.to_string(),
             misc: misc.to_string(),
             index,
+            path: None,
+            id: None,
         }
     }
 
+    /// set the device path, e.g. `/dev/video0`
+    pub fn with_path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// set an ID which stays the same across reboots and replugging
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    pub fn human_name(&self) -> &str {
+        &self.human_name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn misc(&self) -> &str {
+        &self.misc
+    }
+
     pub fn index(&self) -> usize {
         self.index
     }
+
+    /// get the device path, if the platform has one
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    /// get the stable ID, if the device reports one
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// check whether `index` selects this device
+    ///
+    /// A [`CameraIndex::String`] matches the display name, the device path or the stable ID.
+    pub fn matches(&self, index: &CameraIndex) -> bool {
+        match index {
+            CameraIndex::Index(i) => *i as usize == self.index,
+            CameraIndex::String(s) => {
+                self.human_name == *s
+                    || self.path.as_deref() == Some(s.as_str())
+                    || self.id.as_deref() == Some(s.as_str())
+            }
+        }
+    }
+}
+
+impl Default for CameraIndex {
+    fn default() -> Self {
+        CameraIndex::Index(0)
+    }
+}
+
+impl Display for CameraIndex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CameraIndex::Index(i) => write!(f, "{i}"),
+            CameraIndex::String(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+impl From<u32> for CameraIndex {
+    fn from(index: u32) -> Self {
+        CameraIndex::Index(index)
+    }
+}
+
+/// like the `usize` index `GstCamera::new` used to take, fails on indices beyond `u32::MAX`
+impl TryFrom<usize> for CameraIndex {
+    type Error = BevyGstError;
+
+    fn try_from(index: usize) -> Result<Self, Self::Error> {
+        match u32::try_from(index) {
+            Ok(index) => Ok(CameraIndex::Index(index)),
+            Err(why) => Err(BevyGstError::StructureError {
+                structure: "CameraIndex".to_string(),
+                error: format!("Index {index} is too large: {why}"),
+            }),
+        }
+    }
+}
+
+impl From<&str> for CameraIndex {
+    fn from(s: &str) -> Self {
+        CameraIndex::String(s.to_string())
+    }
+}
+
+impl From<String> for CameraIndex {
+    fn from(s: String) -> Self {
+        CameraIndex::String(s)
+    }
+}
+
+/// Clockwise rotation of camera frames
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Hash,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    Reflect,
+)]
+pub enum Rotation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Rotation {
+    fn quarter_turns(&self) -> u8 {
+        match self {
+            Rotation::Deg0 => 0,
+            Rotation::Deg90 => 1,
+            Rotation::Deg180 => 2,
+            Rotation::Deg270 => 3,
+        }
+    }
+}
+
+/// Orientation of camera frames, e.g. to mirror front facing webcams or to turn mounted cameras
+///
+/// Frames are rotated first, then mirrored.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Hash,
+    Ord,
+    PartialOrd,
+    Eq,
+    PartialEq,
+    Serialize,
+    Deserialize,
+    Reflect,
+)]
+pub struct Orientation {
+    pub rotation: Rotation,
+    /// flip left and right
+    pub mirror_x: bool,
+    /// flip top and bottom
+    pub mirror_y: bool,
 }
 
-#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq)]
+impl Orientation {
+    /// horizontally mirrored, for front facing webcams
+    pub fn mirrored() -> Self {
+        Self {
+            mirror_x: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn rotated(rotation: Rotation) -> Self {
+        Self {
+            rotation,
+            ..Default::default()
+        }
+    }
+
+    /// reduce to a clockwise rotation in quarter turns followed by an optional horizontal flip
+    fn normalized(&self) -> (u8, bool) {
+        // a vertical flip is a horizontal flip after half a turn
+        let turns = self.rotation.quarter_turns() + if self.mirror_y { 2 } else { 0 };
+        (turns % 4, self.mirror_x != self.mirror_y)
+    }
+
+    /// whether frames are left as they are
+    pub fn is_identity(&self) -> bool {
+        self.normalized() == (0, false)
+    }
+
+    /// whether width and height of frames are swapped
+    pub fn swaps_dimensions(&self) -> bool {
+        self.normalized().0 % 2 == 1
+    }
+
+    /// the `video-direction` of a `videoflip` element applying this orientation
+    pub fn video_direction(&self) -> &'static str {
+        match self.normalized() {
+            (0, false) => "identity",
+            (1, false) => "90r",
+            (2, false) => "180",
+            (3, false) => "90l",
+            (0, true) => "horiz",
+            (1, true) => "ul-lr",
+            (2, true) => "vert",
+            _ => "ur-ll",
+        }
+    }
+
+    /// apply the orientation to a decoded frame
+    pub fn apply(&self, image: ImageBuffer<Rgb<u8>, Vec<u8>>) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let (turns, flip) = self.normalized();
+        let image = match turns {
+            0 => image,
+            1 => imageops::rotate90(&image),
+            2 => imageops::rotate180(&image),
+            _ => imageops::rotate270(&image),
+        };
+        if flip {
+            imageops::flip_horizontal(&image)
+        } else {
+            image
+        }
+    }
+}
+
+/// Selects a camera by its position in the device list, or by display name, device path or
+/// stable ID
+#[derive(Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize, Reflect)]
 pub enum CameraIndex {
     Index(u32),
     String(String),
 }
 
+/// Camera hardware controls known to the crate
+#[derive(
+    Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize, Reflect,
+)]
+pub enum KnownCameraControl {
+    Brightness,
+    Contrast,
+    Saturation,
+    Hue,
+    Gamma,
+    Sharpness,
+    BacklightCompensation,
+    PowerLineFrequency,
+    WhiteBalance,
+    WhiteBalanceAuto,
+    Exposure,
+    ExposureAuto,
+    Gain,
+    GainAuto,
+    Focus,
+    FocusAuto,
+    Zoom,
+    /// a platform specific control, by its raw id
+    Other(u32),
+}
+
+impl KnownCameraControl {
+    /// the auto mode toggling this control, if it has one
+    pub fn auto_control(&self) -> Option<KnownCameraControl> {
+        match self {
+            KnownCameraControl::WhiteBalance => Some(KnownCameraControl::WhiteBalanceAuto),
+            KnownCameraControl::Exposure => Some(KnownCameraControl::ExposureAuto),
+            KnownCameraControl::Gain => Some(KnownCameraControl::GainAuto),
+            KnownCameraControl::Focus => Some(KnownCameraControl::FocusAuto),
+            KnownCameraControl::WhiteBalanceAuto
+            | KnownCameraControl::ExposureAuto
+            | KnownCameraControl::GainAuto
+            | KnownCameraControl::FocusAuto => Some(*self),
+            _ => None,
+        }
+    }
+}
+
+impl Display for KnownCameraControl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KnownCameraControl::Other(id) => write!(f, "Other({id:#x})"),
+            known => write!(f, "{known:?}"),
+        }
+    }
+}
+
+/// How the value of a [`CameraControl`] is interpreted
+#[derive(
+    Copy, Clone, Debug, Hash, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize, Reflect,
+)]
+pub enum ControlKind {
+    Integer,
+    Boolean,
+    /// an index into a list of device defined choices
+    Menu,
+}
+
+/// A camera control with its range, default and current value
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize, Reflect)]
+pub struct CameraControl {
+    pub control: KnownCameraControl,
+    /// the name the device reports for this control
+    pub name: String,
+    pub kind: ControlKind,
+    pub min: i32,
+    pub max: i32,
+    pub step: i32,
+    pub default: i32,
+    pub value: i32,
+    /// false when the control is currently overridden, e.g. by its auto mode
+    pub active: bool,
+    /// the values a menu control accepts, empty for other kinds or when the device does not say
+    #[serde(default)]
+    pub menu: Vec<i32>,
+}
+
+#[cfg(feature = "cpu-mjpeg")]
 pub fn mjpeg_to_rgb24(in_buf: &[u8]) -> Result<Vec<u8>, BevyGstError> {
     let mut decoder = jpeg_decoder::Decoder::new(in_buf);
 
@@ -224,60 +605,20 @@ pub fn buf_yuyv422_to_rgb(data: &[u8], dest: &mut [u8], rgba: bool) -> Result<()
         });
     }
 
-    let iter = data.chunks_exact(4);
-
-    if rgba {
-        let mut iter = iter
-            .flat_map(|yuyv| {
-                let y1 = i32::from(yuyv[0]);
-                let u = i32::from(yuyv[1]);
-                let y2 = i32::from(yuyv[2]);
-                let v = i32::from(yuyv[3]);
-                let pixel1 = yuyv444_to_rgba(y1, u, v);
-                let pixel2 = yuyv444_to_rgba(y2, u, v);
-                [pixel1, pixel2]
-            })
-            .flatten();
-        for i in dest.iter_mut().take(rgb_buf_size) {
-            *i = match iter.next() {
-                Some(v) => v,
-                None => {
-                    return Err(BevyGstError::ProcessFrameError {
-                        src: FrameFormat::YUYV,
-                        destination: "RGBA8888".to_string(),
-                        error: "Ran out of RGBA YUYV values! (this should not happen, please file an issue: l1npengtul/nokhwa)".to_string()
-                    })
-                }
-            }
-        }
+    let format = if rgba {
+        PixelFormat::Rgba8
     } else {
-        let mut iter = iter
-            .flat_map(|yuyv| {
-                let y1 = i32::from(yuyv[0]);
-                let u = i32::from(yuyv[1]);
-                let y2 = i32::from(yuyv[2]);
-                let v = i32::from(yuyv[3]);
-                let pixel1 = yuyv444_to_rgb(y1, u, v);
-                let pixel2 = yuyv444_to_rgb(y2, u, v);
-                [pixel1, pixel2]
-            })
-            .flatten();
-
-        for i in dest.iter_mut().take(rgb_buf_size) {
-            *i = match iter.next() {
-                Some(v) => v,
-                None => {
-                    return Err(BevyGstError::ProcessFrameError {
-                        src: FrameFormat::YUYV,
-                        destination: "RGB888".to_string(),
-                        error: "Ran out of RGB YUYV values! (this should not happen, please file an issue: l1npengtul/nokhwa)".to_string()
-                    })
-                }
-            }
-        }
-    }
-
-    Ok(())
+        PixelFormat::Rgb8
+    };
+    // one row of pixel pairs
+    convert(
+        &RawFrame::Yuyv(Plane::new(data, data.len())),
+        (data.len() / 2) as u32,
+        1,
+        dest,
+        format,
+        Colorimetry::default(),
+    )
 }
 
 // equation from https://en.wikipedia.org/wiki/YUV#Converting_between_Y%E2%80%B2UV_and_RGB
@@ -291,9 +632,9 @@ pub fn yuyv444_to_rgb(y: i32, u: i32, v: i32) -> [u8; 3] {
     let c298 = (y - 16) * 298;
     let d = u - 128;
     let e = v - 128;
-    let r = ((c298 + 409 * e + 128) >> 8) as u8;
-    let g = ((c298 - 100 * d - 208 * e + 128) >> 8) as u8;
-    let b = ((c298 + 516 * d + 128) >> 8) as u8;
+    let r = ((c298 + 409 * e + 128) >> 8).clamp(0, 255) as u8;
+    let g = ((c298 - 100 * d - 208 * e + 128) >> 8).clamp(0, 255) as u8;
+    let b = ((c298 + 516 * d + 128) >> 8).clamp(0, 255) as u8;
     [r, g, b]
 }
 
@@ -308,3 +649,68 @@ pub fn yuyv444_to_rgba(y: i32, u: i32, v: i32) -> [u8; 4] {
     let [r, g, b] = yuyv444_to_rgb(y, u, v);
     [r, g, b, 255]
 }
+
+/// Device selection, format and controls of a camera, to persist user choices across runs
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, Reflect)]
+pub struct CameraConfig {
+    pub device: CameraIndex,
+    pub format: CameraFormat,
+    #[serde(default)]
+    pub orientation: Orientation,
+    /// hardware control values, applied after the camera is opened
+    #[serde(default)]
+    pub controls: BTreeMap<KnownCameraControl, i32>,
+}
+
+impl CameraConfig {
+    pub fn new(device: CameraIndex, format: CameraFormat) -> Self {
+        Self {
+            device,
+            format,
+            orientation: Orientation::default(),
+            controls: BTreeMap::new(),
+        }
+    }
+
+    /// load a config from a RON file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BevyGstError> {
+        let path = path.as_ref();
+        let content = match fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(why) => {
+                return Err(BevyGstError::ConfigError(
+                    path.display().to_string(),
+                    why.to_string(),
+                ));
+            }
+        };
+        match ron::from_str(&content) {
+            Ok(config) => Ok(config),
+            Err(why) => Err(BevyGstError::ConfigError(
+                path.display().to_string(),
+                why.to_string(),
+            )),
+        }
+    }
+
+    /// save the config to a RON file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BevyGstError> {
+        let path = path.as_ref();
+        let content = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(c) => c,
+            Err(why) => {
+                return Err(BevyGstError::ConfigError(
+                    path.display().to_string(),
+                    why.to_string(),
+                ));
+            }
+        };
+        if let Err(why) = fs::write(path, content) {
+            return Err(BevyGstError::ConfigError(
+                path.display().to_string(),
+                why.to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
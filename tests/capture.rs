@@ -0,0 +1,375 @@
+//! Capture tests against `videotestsrc` and `appsrc`, no camera needed
+#![cfg(feature = "testing")]
+
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use bevy_gstreamer::camera::{
+    EncodedPassthrough, EncodedSample, GstCamera, GstCameraError, GstCameraFailed, GstCameraImage,
+    GstCameraSource, MjpegDecoder, MjpegDecoding,
+};
+use bevy_gstreamer::testing::{
+    TestPattern, app_source, appsrc_camera, headless_app, push_frame, test_camera, update_until,
+    wait_for_frame,
+};
+use bevy_gstreamer::types::{CameraFormat, FrameFormat, Orientation, Rotation};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn init() {
+    gstreamer::init().unwrap();
+}
+
+fn assert_close(actual: [u8; 3], expected: [u8; 3], tolerance: u8) {
+    for (a, e) in actual.iter().zip(expected) {
+        assert!(
+            a.abs_diff(e) <= tolerance,
+            "pixel {actual:?} differs from {expected:?}"
+        );
+    }
+}
+
+#[test]
+fn every_format_produces_frames_of_the_requested_size() {
+    init();
+    for format in [
+        FrameFormat::MJPEG,
+        FrameFormat::YUYV,
+        FrameFormat::UYVY,
+        FrameFormat::YVYU,
+        FrameFormat::NV12,
+        FrameFormat::P010,
+        FrameFormat::GRAY,
+        FrameFormat::RAWRGB,
+    ] {
+        let mut camera = test_camera(
+            TestPattern::Smpte,
+            CameraFormat::new_from(320, 240, format, 30),
+        )
+        .unwrap();
+        camera.open_stream().unwrap();
+        let frame = wait_for_frame(&mut camera, TIMEOUT).unwrap();
+        assert_eq!(frame.dimensions(), (320, 240), "{format}");
+    }
+}
+
+#[test]
+fn solid_color_survives_conversion() {
+    init();
+    for (format, tolerance) in [
+        (FrameFormat::RAWRGB, 0),
+        (FrameFormat::YUYV, 8),
+        (FrameFormat::UYVY, 8),
+        (FrameFormat::YVYU, 8),
+        (FrameFormat::P010, 8),
+        (FrameFormat::MJPEG, 16),
+    ] {
+        let mut camera = test_camera(
+            TestPattern::SolidColor(0xff_ff_00_00),
+            CameraFormat::new_from(64, 48, format, 30),
+        )
+        .unwrap();
+        camera.open_stream().unwrap();
+        let frame = wait_for_frame(&mut camera, TIMEOUT).unwrap();
+        assert_close(frame.get_pixel(32, 24).0, [255, 0, 0], tolerance);
+    }
+}
+
+#[test]
+fn compressed_formats_are_decoded() {
+    init();
+    for (format, encoder) in [
+        (FrameFormat::H264, "x264enc"),
+        (FrameFormat::H265, "x265enc"),
+        (FrameFormat::VP8, "vp8enc"),
+        (FrameFormat::VP9, "vp9enc"),
+    ] {
+        if gstreamer::ElementFactory::find(encoder).is_none() {
+            eprintln!("skip {format}, {encoder} is not installed");
+            continue;
+        }
+        let mut camera = test_camera(
+            TestPattern::SolidColor(0xff_ff_00_00),
+            CameraFormat::new_from(64, 48, format, 30),
+        )
+        .unwrap();
+        camera.open_stream().unwrap();
+        let frame = wait_for_frame(&mut camera, TIMEOUT).unwrap();
+        assert_eq!(frame.dimensions(), (64, 48), "{format}");
+        assert_close(frame.get_pixel(32, 24).0, [255, 0, 0], 24);
+    }
+}
+
+fn wait_for_samples(camera: &mut GstCamera, count: usize) -> Vec<EncodedSample> {
+    let start = Instant::now();
+    let mut samples = vec![];
+    while samples.len() < count && start.elapsed() < TIMEOUT {
+        samples.extend(camera.encoded_samples().unwrap());
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    samples
+}
+
+#[test]
+fn encoded_samples_alongside_frames() {
+    init();
+    let mut camera = test_camera(
+        TestPattern::Smpte,
+        CameraFormat::new_from(64, 48, FrameFormat::MJPEG, 30),
+    )
+    .unwrap();
+    camera
+        .set_encoded_passthrough(EncodedPassthrough::Alongside)
+        .unwrap();
+    camera.open_stream().unwrap();
+
+    wait_for_frame(&mut camera, TIMEOUT).unwrap();
+    let samples = wait_for_samples(&mut camera, 3);
+    assert!(samples.len() >= 3);
+    assert!(samples[0].caps.as_ref().unwrap().starts_with("image/jpeg"));
+    assert!(samples[1..].iter().all(|sample| sample.caps.is_none()));
+    for sample in &samples {
+        assert_eq!(sample.data[..2], [0xff, 0xd8], "JPEG start of image");
+        assert!(sample.keyframe);
+    }
+    assert!(samples.windows(2).all(|pair| pair[0].pts < pair[1].pts));
+}
+
+#[test]
+fn encoded_samples_only_skip_decoding() {
+    init();
+    if gstreamer::ElementFactory::find("x264enc").is_none() {
+        eprintln!("skip, x264enc is not installed");
+        return;
+    }
+    let mut camera = test_camera(
+        TestPattern::Smpte,
+        CameraFormat::new_from(64, 48, FrameFormat::H264, 30),
+    )
+    .unwrap();
+    camera
+        .set_encoded_passthrough(EncodedPassthrough::Only)
+        .unwrap();
+    camera.open_stream().unwrap();
+
+    let samples = wait_for_samples(&mut camera, 5);
+    assert!(samples.len() >= 5);
+    assert!(samples[0].keyframe);
+    assert!(
+        samples[0]
+            .caps
+            .as_ref()
+            .unwrap()
+            .starts_with("video/x-h264")
+    );
+    // annex B start code
+    let data = &samples[0].data;
+    assert!(data.starts_with(&[0, 0, 1]) || data.starts_with(&[0, 0, 0, 1]));
+    assert!(camera.poll_frame().unwrap().is_none());
+}
+
+#[test]
+fn mjpeg_decoders_agree() {
+    init();
+    for decoder in [MjpegDecoder::Cpu, MjpegDecoder::Jpegdec, MjpegDecoder::Auto] {
+        let mut camera = test_camera(
+            TestPattern::SolidColor(0xff_00_00_ff),
+            CameraFormat::new_from(64, 48, FrameFormat::MJPEG, 30),
+        )
+        .unwrap();
+        camera
+            .set_mjpeg_decoding(MjpegDecoding {
+                decoder,
+                threads: 2,
+            })
+            .unwrap();
+        camera.open_stream().unwrap();
+        let frame = wait_for_frame(&mut camera, TIMEOUT).unwrap();
+        assert_eq!(frame.dimensions(), (64, 48), "{decoder:?}");
+        assert_close(frame.get_pixel(32, 24).0, [0, 0, 255], 16);
+    }
+}
+
+#[test]
+fn gray_frames_replicate_luma() {
+    init();
+    for (pattern, expected) in [(TestPattern::Black, 16), (TestPattern::White, 235)] {
+        let mut camera = test_camera(
+            pattern,
+            CameraFormat::new_from(64, 48, FrameFormat::GRAY, 30),
+        )
+        .unwrap();
+        camera.open_stream().unwrap();
+        let frame = wait_for_frame(&mut camera, TIMEOUT).unwrap();
+        let [r, g, b] = frame.get_pixel(10, 10).0;
+        assert!(r == g && g == b);
+        // full or limited range depending on the videotestsrc version
+        assert!(r.abs_diff(expected) <= 20, "{pattern:?} gave {r}");
+    }
+}
+
+#[test]
+fn appsrc_frames_arrive_unchanged() {
+    init();
+    let mut camera = appsrc_camera(CameraFormat::new_from(4, 2, FrameFormat::RAWRGB, 30)).unwrap();
+    camera.open_stream().unwrap();
+
+    let data: Vec<u8> = (0..4 * 2 * 3).map(|i| i as u8 * 10).collect();
+    push_frame(&camera, data.clone()).unwrap();
+    let frame = wait_for_frame(&mut camera, TIMEOUT).unwrap();
+    assert_eq!(frame.into_raw(), data);
+}
+
+#[test]
+fn mirrored_orientation_flips_frames() {
+    init();
+    let mut camera = appsrc_camera(CameraFormat::new_from(4, 1, FrameFormat::RAWRGB, 30)).unwrap();
+    camera.set_orientation(Orientation::mirrored()).unwrap();
+    camera.open_stream().unwrap();
+
+    push_frame(
+        &camera,
+        [[255, 0, 0], [0, 0, 0], [0, 0, 0], [0, 0, 255]].concat(),
+    )
+    .unwrap();
+    let frame = wait_for_frame(&mut camera, TIMEOUT).unwrap();
+    assert_eq!(frame.get_pixel(0, 0).0, [0, 0, 255]);
+    assert_eq!(frame.get_pixel(3, 0).0, [255, 0, 0]);
+}
+
+#[test]
+fn rotated_p010_frames_are_turned_in_the_appsink() {
+    init();
+    let mut camera = test_camera(
+        TestPattern::Smpte,
+        CameraFormat::new_from(64, 48, FrameFormat::P010, 30),
+    )
+    .unwrap();
+    camera
+        .set_orientation(Orientation {
+            rotation: Rotation::Deg90,
+            ..Default::default()
+        })
+        .unwrap();
+    camera.open_stream().unwrap();
+    let frame = wait_for_frame(&mut camera, TIMEOUT).unwrap();
+    assert_eq!(frame.dimensions(), (48, 64));
+}
+
+#[test]
+fn with_frame_borrows_the_latest_frame() {
+    init();
+    let mut camera = test_camera(
+        TestPattern::White,
+        CameraFormat::new_from(32, 16, FrameFormat::RAWRGB, 30),
+    )
+    .unwrap();
+    camera.open_stream().unwrap();
+    wait_for_frame(&mut camera, TIMEOUT).unwrap();
+
+    let size = camera
+        .with_frame(|frame| (frame.width(), frame.height()))
+        .unwrap();
+    assert_eq!(size, Some((32, 16)));
+}
+
+#[test]
+fn headless_app_inserts_camera_images() {
+    init();
+    let mut app = headless_app();
+    let mut camera = test_camera(
+        TestPattern::Smpte,
+        CameraFormat::new_from(64, 48, FrameFormat::YUYV, 30),
+    )
+    .unwrap();
+    camera.open_stream().unwrap();
+    let entity = app.world_mut().spawn(camera).id();
+
+    assert!(update_until(&mut app, TIMEOUT, |world| {
+        world.get::<GstCameraImage>(entity).is_some()
+    }));
+
+    let handle = app.world().get::<GstCameraImage>(entity).unwrap().0.clone();
+    let images = app.world().resource::<Assets<Image>>();
+    let image = images.get(&handle).unwrap();
+    assert_eq!(image.size(), UVec2::new(64, 48));
+}
+
+#[test]
+fn camera_image_is_updated_in_place() {
+    init();
+    let mut app = headless_app();
+    let mut camera = test_camera(
+        TestPattern::Smpte,
+        CameraFormat::new_from(64, 48, FrameFormat::YUYV, 30),
+    )
+    .unwrap();
+    camera.open_stream().unwrap();
+    let entity = app.world_mut().spawn(camera).id();
+
+    assert!(update_until(&mut app, TIMEOUT, |world| {
+        world.get::<GstCameraImage>(entity).is_some()
+    }));
+    let handle = app.world().get::<GstCameraImage>(entity).unwrap().0.clone();
+
+    // wait for a later frame to be written into the same asset
+    assert!(update_until(&mut app, TIMEOUT, |world| {
+        world
+            .resource::<Events<AssetEvent<Image>>>()
+            .iter_current_update_events()
+            .any(|event| event.is_modified(&handle))
+    }));
+    assert_eq!(app.world().get::<GstCameraImage>(entity).unwrap().0, handle);
+    assert!(app.world().resource::<Assets<Image>>().contains(&handle));
+}
+
+#[test]
+fn camera_image_leaves_frames_to_the_camera() {
+    init();
+    let mut app = headless_app();
+    let mut camera = appsrc_camera(CameraFormat::new_from(4, 2, FrameFormat::RAWRGB, 30)).unwrap();
+    camera.open_stream().unwrap();
+    push_frame(&camera, vec![7; 4 * 2 * 3]).unwrap();
+    let entity = app.world_mut().spawn(camera).id();
+
+    assert!(update_until(&mut app, TIMEOUT, |world| {
+        world.get::<GstCameraImage>(entity).is_some()
+    }));
+    let mut camera = app.world_mut().get_mut::<GstCamera>(entity).unwrap();
+    let frame = camera.poll_frame().unwrap().unwrap();
+    assert_eq!(frame.get_pixel(0, 0).0, [7, 7, 7]);
+}
+
+#[test]
+fn end_of_stream_is_reported() {
+    init();
+    let mut app = headless_app();
+    let mut camera = appsrc_camera(CameraFormat::new_from(4, 2, FrameFormat::RAWRGB, 30)).unwrap();
+    camera.open_stream().unwrap();
+    app_source(&camera).unwrap().end_of_stream().unwrap();
+    let entity = app.world_mut().spawn(camera).id();
+
+    assert!(update_until(&mut app, TIMEOUT, |world| {
+        world.get::<GstCameraError>(entity).is_some()
+    }));
+    let mut camera = app.world_mut().get_mut::<GstCamera>(entity).unwrap();
+    assert!(camera.frame().is_err());
+}
+
+#[test]
+fn missing_device_reports_failure() {
+    init();
+    let mut app = headless_app();
+    let entity = app
+        .world_mut()
+        .spawn(GstCameraSource::new("no such camera", None))
+        .id();
+    app.update();
+
+    assert!(app.world().get::<GstCamera>(entity).is_none());
+    assert!(app.world().get::<GstCameraError>(entity).is_some());
+    let events = app.world().resource::<Events<GstCameraFailed>>();
+    let failed: Vec<_> = events.iter_current_update_events().collect();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].entity, entity);
+}
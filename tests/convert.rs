@@ -0,0 +1,391 @@
+//! Conversion module against the scalar `yuyv444_to_rgb` reference
+
+use bevy_gstreamer::camera::PixelFormat;
+use bevy_gstreamer::convert::{
+    ColorMatrix, ColorRange, Colorimetry, Plane, RawFrame, convert, convert_portable,
+};
+use bevy_gstreamer::types::yuyv444_to_rgb;
+
+/// deterministic noise covering the whole value range
+fn noise(len: usize, seed: u32) -> Vec<u8> {
+    let mut state = seed;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+/// a frame given per pixel luma and per 2x2 block chroma
+struct Yuv420 {
+    width: usize,
+    height: usize,
+    y: Vec<u8>,
+    u: Vec<u8>,
+    v: Vec<u8>,
+}
+
+impl Yuv420 {
+    fn new(width: usize, height: usize) -> Self {
+        let chroma = width.div_ceil(2) * height.div_ceil(2);
+        Self {
+            width,
+            height,
+            y: noise(width * height, 1),
+            u: noise(chroma, 2),
+            v: noise(chroma, 3),
+        }
+    }
+
+    fn chroma(&self, x: usize, y: usize) -> (u8, u8) {
+        let i = y / 2 * self.width.div_ceil(2) + x / 2;
+        (self.u[i], self.v[i])
+    }
+
+    /// expected RGB from the scalar reference
+    fn reference(&self) -> Vec<u8> {
+        let mut rgb = Vec::with_capacity(self.width * self.height * 3);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (u, v) = self.chroma(x, y);
+                rgb.extend(yuyv444_to_rgb(
+                    i32::from(self.y[y * self.width + x]),
+                    i32::from(u),
+                    i32::from(v),
+                ));
+            }
+        }
+        rgb
+    }
+
+    /// packed 4:2:2 rows padded to `stride`, chroma of odd rows repeats the 4:2:0 block
+    fn packed(&self, stride: usize, order: [usize; 4]) -> Vec<u8> {
+        let mut data = vec![0; stride * self.height];
+        for y in 0..self.height {
+            for pair in 0..self.width.div_ceil(2) {
+                let x = pair * 2;
+                let (u, v) = self.chroma(x, y);
+                let y1 = self.y[y * self.width + x];
+                let y2 = self.y[y * self.width + (x + 1).min(self.width - 1)];
+                let values = [y1, u, y2, v];
+                for (offset, value) in order.iter().zip(values) {
+                    data[y * stride + pair * 4 + offset] = value;
+                }
+            }
+        }
+        data
+    }
+
+    fn plane(data: &[u8], width: usize, height: usize, stride: usize) -> Vec<u8> {
+        let mut plane = vec![0; stride * height];
+        for (row, src) in plane.chunks_mut(stride).zip(data.chunks(width)) {
+            row[..width].copy_from_slice(src);
+        }
+        plane
+    }
+}
+
+fn rgb(frame: &RawFrame, width: usize, height: usize, format: PixelFormat) -> Vec<u8> {
+    let mut dest = vec![0; width * height * format.bytes_per_pixel()];
+    convert(
+        frame,
+        width as u32,
+        height as u32,
+        &mut dest,
+        format,
+        Colorimetry::default(),
+    )
+    .unwrap();
+    dest
+}
+
+#[test]
+fn packed_formats_match_reference() {
+    // odd sizes and padded rows, large enough to be split into bands
+    for (width, height) in [(2, 1), (33, 7), (642, 481)] {
+        let frame = Yuv420::new(width, height);
+        let stride = width.div_ceil(2) * 4 + 12;
+        let expected = frame.reference();
+
+        let yuyv = frame.packed(stride, [0, 1, 2, 3]);
+        let actual = rgb(
+            &RawFrame::Yuyv(Plane::new(&yuyv, stride)),
+            width,
+            height,
+            PixelFormat::Rgb8,
+        );
+        assert!(actual == expected, "yuyv {width}x{height}");
+
+        let uyvy = frame.packed(stride, [1, 0, 3, 2]);
+        let actual = rgb(
+            &RawFrame::Uyvy(Plane::new(&uyvy, stride)),
+            width,
+            height,
+            PixelFormat::Rgb8,
+        );
+        assert!(actual == expected, "uyvy {width}x{height}");
+
+        let yvyu = frame.packed(stride, [0, 3, 2, 1]);
+        let actual = rgb(
+            &RawFrame::Yvyu(Plane::new(&yvyu, stride)),
+            width,
+            height,
+            PixelFormat::Rgb8,
+        );
+        assert!(actual == expected, "yvyu {width}x{height}");
+    }
+}
+
+#[test]
+fn planar_formats_match_reference() {
+    for (width, height) in [(1, 1), (33, 7), (642, 481)] {
+        let frame = Yuv420::new(width, height);
+        let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+        let expected = frame.reference();
+        let y = Yuv420::plane(&frame.y, width, height, width + 5);
+
+        let uv: Vec<u8> = frame
+            .u
+            .iter()
+            .zip(&frame.v)
+            .flat_map(|(u, v)| [*u, *v])
+            .collect();
+        let uv = Yuv420::plane(&uv, chroma_width * 2, chroma_height, chroma_width * 2 + 3);
+        let actual = rgb(
+            &RawFrame::Nv12 {
+                y: Plane::new(&y, width + 5),
+                uv: Plane::new(&uv, chroma_width * 2 + 3),
+            },
+            width,
+            height,
+            PixelFormat::Rgb8,
+        );
+        assert!(actual == expected, "nv12 {width}x{height}");
+
+        let u = Yuv420::plane(&frame.u, chroma_width, chroma_height, chroma_width);
+        let v = Yuv420::plane(&frame.v, chroma_width, chroma_height, chroma_width + 1);
+        let actual = rgb(
+            &RawFrame::I420 {
+                y: Plane::new(&y, width + 5),
+                u: Plane::new(&u, chroma_width),
+                v: Plane::new(&v, chroma_width + 1),
+            },
+            width,
+            height,
+            PixelFormat::Rgb8,
+        );
+        assert!(actual == expected, "i420 {width}x{height}");
+
+        // 10 bit samples in the high bits, the low bits are dropped
+        let p010 = |data: &[u8]| -> Vec<u8> {
+            data.iter()
+                .zip(noise(data.len(), 5))
+                .flat_map(|(value, low)| [low & 0xc0, *value])
+                .collect()
+        };
+        let y = Yuv420::plane(&p010(&frame.y), width * 2, height, width * 2 + 4);
+        let uv: Vec<u8> = frame
+            .u
+            .iter()
+            .zip(&frame.v)
+            .flat_map(|(u, v)| [*u, *v])
+            .collect();
+        let uv = Yuv420::plane(
+            &p010(&uv),
+            chroma_width * 4,
+            chroma_height,
+            chroma_width * 4,
+        );
+        let actual = rgb(
+            &RawFrame::P010 {
+                y: Plane::new(&y, width * 2 + 4),
+                uv: Plane::new(&uv, chroma_width * 4),
+            },
+            width,
+            height,
+            PixelFormat::Rgb8,
+        );
+        assert!(actual == expected, "p010 {width}x{height}");
+    }
+}
+
+#[test]
+fn matrices_and_ranges_match_float_reference() {
+    let (width, height) = (64, 4);
+    let frame = Yuv420::new(width, height);
+    let yuyv = frame.packed(width * 2, [0, 1, 2, 3]);
+    let yuyv = RawFrame::Yuyv(Plane::new(&yuyv, width * 2));
+
+    for (matrix, kr, kb) in [
+        (ColorMatrix::Bt601, 0.299, 0.114),
+        (ColorMatrix::Bt709, 0.2126, 0.0722),
+        (ColorMatrix::Bt2020, 0.2627, 0.0593),
+    ] {
+        for range in [ColorRange::Limited, ColorRange::Full] {
+            let (y_offset, y_scale, c_scale) = match range {
+                ColorRange::Limited => (16.0, 255.0 / 219.0, 255.0 / 224.0),
+                ColorRange::Full => (0.0, 1.0, 1.0),
+            };
+            let mut rgb = vec![0; width * height * 3];
+            let colorimetry = Colorimetry::new(matrix, range);
+            convert(&yuyv, 64, 4, &mut rgb, PixelFormat::Rgb8, colorimetry).unwrap();
+
+            for (i, pixel) in rgb.chunks_exact(3).enumerate() {
+                let (u, v) = frame.chroma(i % width, i / width);
+                let y = (f64::from(frame.y[i]) - y_offset) * y_scale;
+                let (u, v) = (
+                    (f64::from(u) - 128.0) * c_scale,
+                    (f64::from(v) - 128.0) * c_scale,
+                );
+                let kg = 1.0 - kr - kb;
+                let expected = [
+                    y + 2.0 * (1.0 - kr) * v,
+                    y - 2.0 * (1.0 - kb) * kb / kg * u - 2.0 * (1.0 - kr) * kr / kg * v,
+                    y + 2.0 * (1.0 - kb) * u,
+                ];
+                for (actual, expected) in pixel.iter().zip(expected) {
+                    let expected = expected.round().clamp(0.0, 255.0);
+                    assert!(
+                        (f64::from(*actual) - expected).abs() <= 1.0,
+                        "{colorimetry:?} pixel {i}: {actual} != {expected}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn simd_and_portable_paths_agree() {
+    let (width, height) = (67, 5);
+    let frame = Yuv420::new(width, height);
+    let packed = frame.packed(136, [0, 1, 2, 3]);
+    let uv: Vec<u8> = frame
+        .u
+        .iter()
+        .zip(&frame.v)
+        .flat_map(|(u, v)| [*u, *v])
+        .collect();
+    let frames = [
+        RawFrame::Yuyv(Plane::new(&packed, 136)),
+        RawFrame::Nv12 {
+            y: Plane::new(&frame.y, width),
+            uv: Plane::new(&uv, width.div_ceil(2) * 2),
+        },
+    ];
+
+    for raw in &frames {
+        for format in [PixelFormat::Rgb8, PixelFormat::Rgba8] {
+            for matrix in [ColorMatrix::Bt601, ColorMatrix::Bt709, ColorMatrix::Bt2020] {
+                for range in [ColorRange::Limited, ColorRange::Full] {
+                    let colorimetry = Colorimetry::new(matrix, range);
+                    let len = width * height * format.bytes_per_pixel();
+                    let (mut simd, mut portable) = (vec![0; len], vec![0; len]);
+                    let (w, h) = (width as u32, height as u32);
+                    convert(raw, w, h, &mut simd, format, colorimetry).unwrap();
+                    convert_portable(raw, w, h, &mut portable, format, colorimetry).unwrap();
+                    assert!(simd == portable, "{raw:?} {format:?} {colorimetry:?}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn rgba_and_gray_outputs() {
+    let frame = Yuv420::new(66, 9);
+    let yuyv = frame.packed(132, [0, 1, 2, 3]);
+    let yuyv = RawFrame::Yuyv(Plane::new(&yuyv, 132));
+
+    let expected = frame.reference();
+    let rgba = rgb(&yuyv, 66, 9, PixelFormat::Rgba8);
+    for (rgba, rgb) in rgba.chunks_exact(4).zip(expected.chunks_exact(3)) {
+        assert_eq!(&rgba[..3], rgb);
+        assert_eq!(rgba[3], 255);
+    }
+
+    assert_eq!(rgb(&yuyv, 66, 9, PixelFormat::Gray8), frame.y);
+}
+
+#[test]
+fn gray_is_replicated_without_range_expansion() {
+    let luma = noise(40 * 3, 4);
+    let gray = rgb(
+        &RawFrame::Gray(Plane::new(&luma, 40)),
+        40,
+        3,
+        PixelFormat::Rgb8,
+    );
+    for (pixel, luma) in gray.chunks_exact(3).zip(&luma) {
+        assert_eq!(pixel, [*luma; 3]);
+    }
+}
+
+#[test]
+fn short_planes_and_destinations_are_rejected() {
+    let data = vec![0; 100];
+    let mut dest = vec![0; 8 * 8 * 3];
+    let too_short = RawFrame::Gray(Plane::new(&data, 16));
+    assert!(
+        convert(
+            &too_short,
+            8,
+            8,
+            &mut dest,
+            PixelFormat::Rgb8,
+            Colorimetry::default()
+        )
+        .is_err()
+    );
+
+    let narrow_stride = RawFrame::Yuyv(Plane::new(&data, 8));
+    assert!(
+        convert(
+            &narrow_stride,
+            8,
+            2,
+            &mut dest[..48],
+            PixelFormat::Rgb8,
+            Colorimetry::default()
+        )
+        .is_err()
+    );
+
+    let fits = RawFrame::Gray(Plane::new(&data, 10));
+    assert!(
+        convert(
+            &fits,
+            8,
+            8,
+            &mut dest[..10],
+            PixelFormat::Rgb8,
+            Colorimetry::default()
+        )
+        .is_err()
+    );
+    assert!(
+        convert(
+            &fits,
+            8,
+            8,
+            &mut dest,
+            PixelFormat::Rgb8,
+            Colorimetry::default()
+        )
+        .is_ok()
+    );
+    assert!(
+        convert(
+            &fits,
+            10,
+            10,
+            &mut vec![0; 300],
+            PixelFormat::Rgb8,
+            Colorimetry::default()
+        )
+        .is_ok()
+    );
+}
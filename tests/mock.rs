@@ -0,0 +1,61 @@
+//! `MockCamera` tests, no camera needed
+#![cfg(all(feature = "mock", feature = "testing"))]
+
+use std::time::Duration;
+
+use bevy_gstreamer::camera::{GstCamera, GstCameraError, MockCamera};
+use bevy_gstreamer::testing::{headless_app, wait_for_frame};
+use image::{Rgb, RgbImage};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+fn init() {
+    gstreamer::init().unwrap();
+}
+
+#[test]
+fn mock_generator_frames_arrive_in_order() {
+    init();
+    let mock = MockCamera::from_fn(6, 2, |n| RgbImage::from_pixel(6, 2, Rgb([n as u8, 0, 0])));
+    let mut camera = mock.open().unwrap();
+    assert_eq!(camera.camera_info().description(), "Mock camera");
+    camera.open_stream().unwrap();
+
+    let first = wait_for_frame(&mut camera, TIMEOUT).unwrap();
+    let second = wait_for_frame(&mut camera, TIMEOUT).unwrap();
+    assert_eq!(first.dimensions(), (6, 2));
+    assert!(first.get_pixel(5, 1).0[0] < second.get_pixel(5, 1).0[0]);
+}
+
+#[test]
+fn mock_image_sequence_plays_files_in_name_order() {
+    init();
+    let dir = std::env::temp_dir().join("bevy_gstreamer_mock_sequence");
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, value) in [("b.png", 200), ("a.png", 100)] {
+        RgbImage::from_pixel(8, 4, Rgb([value; 3]))
+            .save(dir.join(name))
+            .unwrap();
+    }
+
+    let mut mock = MockCamera::image_sequence(&dir);
+    mock.looping = false;
+    let mut camera = mock.open().unwrap();
+    camera.open_stream().unwrap();
+    let frame = wait_for_frame(&mut camera, TIMEOUT).unwrap();
+    assert_eq!(frame.get_pixel(0, 0).0, [100; 3]);
+}
+
+#[test]
+fn missing_mock_image_reports_failure() {
+    init();
+    let mut app = headless_app();
+    let entity = app
+        .world_mut()
+        .spawn(MockCamera::image("no/such/image.png"))
+        .id();
+    app.update();
+
+    assert!(app.world().get::<GstCamera>(entity).is_none());
+    assert!(app.world().get::<GstCameraError>(entity).is_some());
+}